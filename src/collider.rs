@@ -1,18 +1,363 @@
 mod collider_macros;
+#[cfg(test)]
+mod tests;
+
+use std::cell::RefCell;
 
 use godot::{
     engine::{
-        mesh::ArrayType, BoxMesh, CapsuleMesh, CylinderMesh, Engine, Mesh, MeshInstance3D,
-        SphereMesh,
+        base_material_3d::{CullMode, ShadingMode, Transparency},
+        mesh::{ArrayType, PrimitiveType},
+        ArrayMesh, BoxMesh, CapsuleMesh, CylinderMesh, Engine, Image, Material, Mesh,
+        MeshInstance3D, ProjectSettings, SphereMesh, StandardMaterial3D, SurfaceTool,
     },
     obj::IndexEnum,
     prelude::*,
 };
-use rapier3d::{na::Point3, prelude::*};
+use rapier3d::{
+    na::{DMatrix, Point3, Unit},
+    prelude::*,
+};
+
+use crate::{
+    collider,
+    convex_decomposition::R3DConvexDecomposition,
+    rigid_body::{BodyType, R3DRigidBody},
+    shape::R3DShape,
+    utils::perpendicular,
+};
+
+pub use self::collider_macros::{GizmoHandle, R3DCollider};
+
+/// Project setting with a material that replaces the built-in collider preview
+/// material.
+pub const DEBUG_MATERIAL_SETTING: &str = "gdrapier3d/editor/collider_material";
+
+thread_local! {
+    /// Built-in preview materials for dynamic, kinematic and fixed or static
+    /// colliders, created on first use.
+    static DEBUG_MATERIALS: RefCell<[Option<Gd<Material>>; 3]> =
+        const { RefCell::new([None, None, None]) };
+}
+
+/// Material for the editor collider preview. Colored by the type of the body
+/// the collider belongs to, or `None` for static colliders.
+pub fn debug_material(body_type: Option<BodyType>) -> Gd<Material> {
+    let project_settings = ProjectSettings::singleton();
+    if project_settings.has_setting(DEBUG_MATERIAL_SETTING.into()) {
+        let path = project_settings
+            .get_setting(DEBUG_MATERIAL_SETTING.into())
+            .to::<GString>();
+        if !path.is_empty() {
+            if let Ok(material) = try_load::<Material>(path.clone()) {
+                return material;
+            }
+            godot_warn!("Could not load the collider material at {}", path);
+        }
+    }
+
+    let (index, color) = match body_type {
+        Some(BodyType::Dynamic) => (0, Color::from_rgba(1.0, 0.6, 0.2, 0.3)),
+        Some(BodyType::Kinematic | BodyType::KinematicVelocity) => {
+            (1, Color::from_rgba(0.4, 0.5, 1.0, 0.3))
+        }
+        Some(BodyType::Fixed) | None => (2, Color::from_rgba(0.5, 0.8, 0.5, 0.3)),
+    };
+    DEBUG_MATERIALS.with(|materials| {
+        materials.borrow_mut()[index]
+            .get_or_insert_with(|| {
+                let mut material = StandardMaterial3D::new_gd();
+                material.set_shading_mode(ShadingMode::UNSHADED);
+                material.set_transparency(Transparency::ALPHA);
+                material.set_cull_mode(CullMode::DISABLED);
+                material.set_albedo(color);
+                material.upcast()
+            })
+            .clone()
+    })
+}
+
+/// Releases the built-in preview materials. Called when the editor plugin
+/// is unloaded, before Godot shuts down.
+pub fn clear_debug_materials() {
+    DEBUG_MATERIALS.with(|materials| *materials.borrow_mut() = [None, None, None]);
+}
+
+/// Subdivisions used when a curved shape has to be approximated because of a
+/// non-uniform scale.
+pub const SCALED_SHAPE_SUBDIVISIONS: u32 = 16;
+
+pub fn scaled_convex_mesh(
+    (vertices, indices): (Vec<Point3<f32>>, Vec<[u32; 3]>),
+    scale: Vector3,
+) -> Option<SharedShape> {
+    let vertices = vertices
+        .into_iter()
+        .map(|v| Point3::new(v.x * scale.x, v.y * scale.y, v.z * scale.z))
+        .collect::<Vec<_>>();
+    SharedShape::convex_mesh(vertices, &indices)
+}
+
+/// Relative difference up to which two scale axes count as equal. Scales read
+/// from a rotated basis are slightly off even when they were set uniformly.
+const SCALE_TOLERANCE: f32 = 1e-4;
+
+fn same_scale(a: f32, b: f32) -> bool {
+    (a - b).abs() <= SCALE_TOLERANCE * a.abs().max(b.abs())
+}
+
+/// Whether balls and capsules represent this scale exactly.
+pub fn is_uniform_scale(scale: Vector3) -> bool {
+    same_scale(scale.x, scale.y) && same_scale(scale.x, scale.z)
+}
+
+/// Whether shapes that are round around the Y axis, like cylinders and cones,
+/// represent this scale exactly. Stretching along the axis keeps them exact.
+pub fn is_uniform_xz_scale(scale: Vector3) -> bool {
+    same_scale(scale.x, scale.z)
+}
+
+pub fn ball_shape(radius: f32, scale: Vector3) -> Option<SharedShape> {
+    if is_uniform_scale(scale) {
+        Some(SharedShape::ball(radius * scale.x))
+    } else {
+        scaled_convex_mesh(
+            Ball::new(radius).to_trimesh(SCALED_SHAPE_SUBDIVISIONS, SCALED_SHAPE_SUBDIVISIONS),
+            scale,
+        )
+    }
+}
+
+pub fn ball_debug_mesh(radius: f32) -> Gd<Mesh> {
+    let mut mesh = SphereMesh::new_gd();
+    mesh.set_radius(radius);
+    mesh.set_height(radius * 2.0);
+    mesh.upcast()
+}
+
+pub fn cuboid_shape(dimensions: Vector3, scale: Vector3) -> Option<SharedShape> {
+    Some(SharedShape::cuboid(
+        scale.x * dimensions.x / 2.,
+        scale.y * dimensions.y / 2.,
+        scale.z * dimensions.z / 2.,
+    ))
+}
+
+pub fn cuboid_debug_mesh(dimensions: Vector3) -> Gd<Mesh> {
+    let mut mesh = BoxMesh::new_gd();
+    mesh.set_size(dimensions);
+    mesh.upcast()
+}
+
+pub fn capsule_shape(radius: f32, half_height: f32, scale: Vector3) -> Option<SharedShape> {
+    if is_uniform_scale(scale) {
+        Some(SharedShape::capsule_y(
+            half_height * scale.x,
+            radius * scale.x,
+        ))
+    } else {
+        scaled_convex_mesh(
+            Capsule::new_y(half_height, radius)
+                .to_trimesh(SCALED_SHAPE_SUBDIVISIONS, SCALED_SHAPE_SUBDIVISIONS),
+            scale,
+        )
+    }
+}
 
-use crate::{collider, rigid_body::R3DRigidBody};
+pub fn capsule_debug_mesh(radius: f32, half_height: f32) -> Gd<Mesh> {
+    let mut mesh = CapsuleMesh::new_gd();
+    mesh.set_height(half_height * 2.0 + radius * 2.0);
+    mesh.set_radius(radius);
+    mesh.upcast()
+}
+
+pub fn cylinder_shape(radius: f32, half_height: f32, scale: Vector3) -> Option<SharedShape> {
+    if is_uniform_xz_scale(scale) {
+        Some(SharedShape::cylinder(
+            half_height * scale.y,
+            radius * scale.x,
+        ))
+    } else {
+        scaled_convex_mesh(
+            Cylinder::new(half_height, radius).to_trimesh(SCALED_SHAPE_SUBDIVISIONS),
+            scale,
+        )
+    }
+}
+
+pub fn cylinder_debug_mesh(radius: f32, half_height: f32) -> Gd<Mesh> {
+    let mut mesh = CylinderMesh::new_gd();
+    mesh.set_top_radius(radius);
+    mesh.set_bottom_radius(radius);
+    mesh.set_height(half_height * 2.0);
+    mesh.upcast()
+}
+
+pub fn trimesh_shape(
+    mesh: &Gd<Mesh>,
+    surfaces: &[i32],
+    scale: Vector3,
+    flags: TriMeshFlags,
+) -> Option<SharedShape> {
+    let (vertices, indices) = mesh_triangles(mesh, surfaces, scale)?;
+    Some(SharedShape::trimesh_with_flags(vertices, indices, flags))
+}
+
+/// Debug mesh of a shape built as a convex hull.
+pub fn hull_debug_mesh(shape: Option<SharedShape>) -> Gd<Mesh> {
+    match shape
+        .as_ref()
+        .and_then(|shape| shape.as_convex_polyhedron())
+    {
+        Some(hull) => {
+            let (vertices, indices) = hull.to_trimesh();
+            trimesh_debug_mesh(&vertices, &indices)
+        }
+        None => Mesh::new_gd(),
+    }
+}
+
+/// Whether a surface holds triangles. Only ArrayMesh can report otherwise,
+/// other meshes are assumed to be made of triangles.
+fn is_triangle_surface(mesh: &Gd<Mesh>, surface: i32) -> bool {
+    match mesh.clone().try_cast::<ArrayMesh>() {
+        Ok(array_mesh) => {
+            array_mesh.surface_get_primitive_type(surface) == PrimitiveType::TRIANGLES
+        }
+        Err(_) => true,
+    }
+}
+
+/// Merges the triangles of a mesh's surfaces, wound counterclockwise as parry
+/// expects. All triangle surfaces are read when `surfaces` is empty.
+pub fn mesh_triangles(
+    mesh: &Gd<Mesh>,
+    surfaces: &[i32],
+    scale: Vector3,
+) -> Option<(Vec<Point3<f32>>, Vec<[u32; 3]>)> {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for surface in 0..mesh.get_surface_count() {
+        if !surfaces.is_empty() && !surfaces.contains(&surface) {
+            continue;
+        }
+        if !is_triangle_surface(mesh, surface) {
+            continue;
+        }
+
+        let arrays = mesh.surface_get_arrays(surface);
+        let surface_vertices = arrays
+            .get(ArrayType::VERTEX.to_index())
+            .try_to::<PackedVector3Array>()
+            .unwrap_or_default();
+        let surface_indices = arrays
+            .get(ArrayType::INDEX.to_index())
+            .try_to::<PackedInt32Array>()
+            .unwrap_or_default();
+
+        let offset = vertices.len() as u32;
+        vertices.extend(
+            surface_vertices
+                .as_slice()
+                .iter()
+                .map(|v| Point3::new(v.x * scale.x, v.y * scale.y, v.z * scale.z)),
+        );
+
+        if surface_indices.is_empty() {
+            // Non-indexed surfaces list every triangle's vertices in order.
+            // Godot treats clockwise triangles as front facing, so the
+            // winding is swapped for parry.
+            let count = surface_vertices.len() as u32 / 3 * 3;
+            indices.extend(
+                (0..count)
+                    .step_by(3)
+                    .map(|i| [offset + i, offset + i + 2, offset + i + 1]),
+            );
+        } else {
+            indices.extend(surface_indices.as_slice().chunks_exact(3).map(|triangle| {
+                [
+                    offset + triangle[0] as u32,
+                    offset + triangle[2] as u32,
+                    offset + triangle[1] as u32,
+                ]
+            }));
+        }
+    }
+
+    if indices.is_empty() {
+        None
+    } else {
+        Some((vertices, indices))
+    }
+}
 
-pub use self::collider_macros::R3DCollider;
+fn scale_point(point: Vector3, scale: Vector3) -> Point3<f32> {
+    Point3::new(point.x * scale.x, point.y * scale.y, point.z * scale.z)
+}
+
+/// Builds an editor mesh of line segments. Consecutive points are connected
+/// when `strip` is set, otherwise every pair of points is its own segment.
+fn lines_debug_mesh(points: &[Vector3], strip: bool) -> Gd<Mesh> {
+    if points.len() < 2 {
+        return Mesh::new_gd();
+    }
+
+    let mut surface_tool = SurfaceTool::new_gd();
+    surface_tool.begin(if strip {
+        PrimitiveType::LINE_STRIP
+    } else {
+        PrimitiveType::LINES
+    });
+    for point in points {
+        surface_tool.add_vertex(*point);
+    }
+    surface_tool
+        .commit()
+        .map(|mesh| mesh.upcast())
+        .unwrap_or_else(Mesh::new_gd)
+}
+
+/// Builds an editor mesh from triangles generated by parry.
+pub fn trimesh_debug_mesh(vertices: &[Point3<f32>], indices: &[[u32; 3]]) -> Gd<Mesh> {
+    let mut surface_tool = SurfaceTool::new_gd();
+    surface_tool.begin(PrimitiveType::TRIANGLES);
+    for [a, b, c] in indices {
+        // Godot treats clockwise triangles as front facing
+        for index in [a, c, b] {
+            let vertex = vertices[*index as usize];
+            surface_tool.add_vertex(Vector3::new(vertex.x, vertex.y, vertex.z));
+        }
+    }
+    surface_tool.generate_normals();
+    surface_tool
+        .commit()
+        .map(|mesh| mesh.upcast())
+        .unwrap_or_else(Mesh::new_gd)
+}
+
+fn dimension_handles(dimensions: Vector3) -> Vec<GizmoHandle> {
+    vec![
+        GizmoHandle::new("dimensions", Vector3::RIGHT, dimensions.x / 2.0),
+        GizmoHandle::new("dimensions", Vector3::UP, dimensions.y / 2.0),
+        GizmoHandle::new("dimensions", Vector3::BACK, dimensions.z / 2.0),
+    ]
+}
+
+fn resize_dimension(dimensions: Vector3, handle: usize, distance: f32) -> Vector3 {
+    match handle {
+        0 => Vector3::new(distance * 2.0, dimensions.y, dimensions.z),
+        1 => Vector3::new(dimensions.x, distance * 2.0, dimensions.z),
+        _ => Vector3::new(dimensions.x, dimensions.y, distance * 2.0),
+    }
+}
+
+fn radius_height_handles(radius: f32, half_height: f32) -> Vec<GizmoHandle> {
+    vec![
+        GizmoHandle::new("radius", Vector3::RIGHT, radius),
+        GizmoHandle::new("half_height", Vector3::UP, half_height),
+    ]
+}
 
 collider!(
     R3DBallCollider,
@@ -20,15 +365,20 @@ collider!(
     radius: f32 = 0.5,
 );
 impl R3DCollider for R3DBallCollider {
-    fn get_shape(&self, scale: f32) -> Option<SharedShape> {
-        Some(SharedShape::ball(self.radius * scale))
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        vec![GizmoHandle::new("radius", Vector3::RIGHT, self.radius)]
+    }
+
+    fn set_gizmo_handle(&mut self, _handle: usize, distance: f32) {
+        self.radius(distance);
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        ball_shape(self.radius, scale)
     }
 
     fn get_debug_mesh(&self) -> Gd<Mesh> {
-        let mut mesh = SphereMesh::new_gd();
-        mesh.set_radius(self.radius);
-        mesh.set_height(self.radius * 2.0);
-        mesh.upcast()
+        ball_debug_mesh(self.radius)
     }
 }
 
@@ -40,18 +390,24 @@ collider!(
     half_height: f32 = 1.0,
 );
 impl R3DCollider for R3DCapsuleCollider {
-    fn get_shape(&self, scale: f32) -> Option<SharedShape> {
-        Some(SharedShape::capsule_y(
-            self.half_height * scale,
-            self.radius * scale,
-        ))
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        // The height handle sits on the tip of the cap
+        radius_height_handles(self.radius, self.half_height + self.radius)
+    }
+
+    fn set_gizmo_handle(&mut self, handle: usize, distance: f32) {
+        match handle {
+            0 => self.radius(distance),
+            _ => self.half_height((distance - self.radius).max(0.0)),
+        }
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        capsule_shape(self.radius, self.half_height, scale)
     }
 
     fn get_debug_mesh(&self) -> Gd<Mesh> {
-        let mut mesh = CapsuleMesh::new_gd();
-        mesh.set_height(self.half_height * 2.0 + self.radius * 2.0);
-        mesh.set_radius(self.radius);
-        mesh.upcast()
+        capsule_debug_mesh(self.radius, self.half_height)
     }
 }
 
@@ -61,18 +417,24 @@ collider!(
     dimensions: Vector3 = Vector3::new(0.5, 0.5, 0.5),
 );
 impl R3DCollider for R3DCuboidCollider {
-    fn get_shape(&self, scale: f32) -> Option<SharedShape> {
-        Some(SharedShape::cuboid(
-            scale * self.dimensions.x / 2.,
-            scale * self.dimensions.y / 2.,
-            scale * self.dimensions.z / 2.,
-        ))
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        dimension_handles(self.dimensions)
+    }
+
+    fn set_gizmo_handle(&mut self, handle: usize, distance: f32) {
+        self.dimensions(resize_dimension(self.dimensions, handle, distance));
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        cuboid_shape(self.dimensions, scale)
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
     }
 
     fn get_debug_mesh(&self) -> Gd<Mesh> {
-        let mut mesh = BoxMesh::new_gd();
-        mesh.set_size(self.dimensions);
-        mesh.upcast()
+        cuboid_debug_mesh(self.dimensions)
     }
 }
 
@@ -84,62 +446,588 @@ collider!(
     half_height: f32 = 1.0,
 );
 impl R3DCollider for R3DCylinderCollider {
-    fn get_shape(&self, scale: f32) -> Option<SharedShape> {
-        Some(SharedShape::cylinder(
-            self.half_height * scale,
-            self.radius * scale,
-        ))
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        radius_height_handles(self.radius, self.half_height)
+    }
+
+    fn set_gizmo_handle(&mut self, handle: usize, distance: f32) {
+        match handle {
+            0 => self.radius(distance),
+            _ => self.half_height(distance),
+        }
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        cylinder_shape(self.radius, self.half_height, scale)
+    }
+
+    fn is_scale_exact(&self, scale: Vector3) -> bool {
+        is_uniform_xz_scale(scale)
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        cylinder_debug_mesh(self.radius, self.half_height)
+    }
+}
+
+collider!(
+    R3DConeCollider,
+    #[export]
+    radius: f32 = 0.5,
+    #[export]
+    half_height: f32 = 0.5,
+);
+impl R3DCollider for R3DConeCollider {
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        radius_height_handles(self.radius, self.half_height)
+    }
+
+    fn set_gizmo_handle(&mut self, handle: usize, distance: f32) {
+        match handle {
+            0 => self.radius(distance),
+            _ => self.half_height(distance),
+        }
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        if self.is_scale_exact(scale) {
+            Some(SharedShape::cone(
+                self.half_height * scale.y,
+                self.radius * scale.x,
+            ))
+        } else {
+            scaled_convex_mesh(
+                Cone::new(self.half_height, self.radius).to_trimesh(SCALED_SHAPE_SUBDIVISIONS),
+                scale,
+            )
+        }
     }
 
     fn get_debug_mesh(&self) -> Gd<Mesh> {
         let mut mesh = CylinderMesh::new_gd();
-        mesh.set_top_radius(self.radius);
+        mesh.set_top_radius(0.0);
         mesh.set_bottom_radius(self.radius);
         mesh.set_height(self.half_height * 2.0);
         mesh.upcast()
     }
+
+    fn is_scale_exact(&self, scale: Vector3) -> bool {
+        is_uniform_xz_scale(scale)
+    }
+}
+
+collider!(
+    R3DRoundCuboidCollider,
+    #[export]
+    dimensions: Vector3 = Vector3::new(0.5, 0.5, 0.5),
+    #[export]
+    border_radius: f32 = 0.05,
+);
+impl R3DCollider for R3DRoundCuboidCollider {
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        dimension_handles(self.dimensions)
+    }
+
+    fn set_gizmo_handle(&mut self, handle: usize, distance: f32) {
+        self.dimensions(resize_dimension(self.dimensions, handle, distance));
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        // The dimensions include the rounded border
+        let border_radius = self.border_radius * scale.x.min(scale.y).min(scale.z);
+        Some(SharedShape::round_cuboid(
+            (scale.x * self.dimensions.x / 2. - border_radius).max(0.0),
+            (scale.y * self.dimensions.y / 2. - border_radius).max(0.0),
+            (scale.z * self.dimensions.z / 2. - border_radius).max(0.0),
+            border_radius,
+        ))
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        cuboid_debug_mesh(self.dimensions)
+    }
+}
+
+collider!(
+    R3DRoundCylinderCollider,
+    #[export]
+    radius: f32 = 0.5,
+    #[export]
+    half_height: f32 = 1.0,
+    #[export]
+    border_radius: f32 = 0.05,
+);
+impl R3DCollider for R3DRoundCylinderCollider {
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        radius_height_handles(self.radius, self.half_height)
+    }
+
+    fn set_gizmo_handle(&mut self, handle: usize, distance: f32) {
+        match handle {
+            0 => self.radius(distance),
+            _ => self.half_height(distance),
+        }
+    }
+
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        // The radius and height include the rounded border
+        let border_radius = self.border_radius * scale.x.min(scale.y).min(scale.z);
+        let inner = Vector3::new(
+            (self.radius * scale.x - border_radius).max(0.0),
+            (self.half_height * scale.y - border_radius).max(0.0),
+            (self.radius * scale.z - border_radius).max(0.0),
+        );
+        if self.is_scale_exact(scale) {
+            Some(SharedShape::round_cylinder(inner.y, inner.x, border_radius))
+        } else {
+            let (vertices, indices) = Cylinder::new(1.0, 1.0).to_trimesh(SCALED_SHAPE_SUBDIVISIONS);
+            let vertices = vertices
+                .into_iter()
+                .map(|v| Point3::new(v.x * inner.x, v.y * inner.y, v.z * inner.z))
+                .collect::<Vec<_>>();
+            SharedShape::round_convex_mesh(vertices, &indices, border_radius)
+        }
+    }
+
+    fn is_scale_exact(&self, scale: Vector3) -> bool {
+        is_uniform_xz_scale(scale)
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        cylinder_debug_mesh(self.radius, self.half_height)
+    }
+}
+
+collider!(
+    R3DSegmentCollider,
+    #[export]
+    a: Vector3 = Vector3::new(0.0, -0.5, 0.0),
+    #[export]
+    b: Vector3 = Vector3::new(0.0, 0.5, 0.0),
+);
+impl R3DCollider for R3DSegmentCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        Some(SharedShape::segment(
+            scale_point(self.a, scale),
+            scale_point(self.b, scale),
+        ))
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        lines_debug_mesh(&[self.a, self.b], false)
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DTriangleCollider,
+    #[export]
+    a: Vector3 = Vector3::new(-0.5, 0.0, 0.5),
+    #[export]
+    b: Vector3 = Vector3::new(0.5, 0.0, 0.5),
+    #[export]
+    c: Vector3 = Vector3::new(0.0, 0.0, -0.5),
+);
+impl R3DCollider for R3DTriangleCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        Some(SharedShape::triangle(
+            scale_point(self.a, scale),
+            scale_point(self.b, scale),
+            scale_point(self.c, scale),
+        ))
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        let vertices = [self.a, self.b, self.c].map(|v| scale_point(v, Vector3::ONE));
+        // Triangles collide from both sides so draw both faces
+        trimesh_debug_mesh(&vertices, &[[0, 1, 2], [0, 2, 1]])
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DHalfSpaceCollider,
+    #[export]
+    normal: Vector3 = Vector3::UP,
+);
+impl R3DCollider for R3DHalfSpaceCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        // Normals transform with the inverse scale
+        let normal = self.normal / scale;
+        Some(SharedShape::halfspace(Unit::try_new(
+            vector![normal.x, normal.y, normal.z],
+            0.0001,
+        )?))
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        // Only a patch of the infinite plane is drawn
+        const EXTENT: f32 = 50.0;
+        let normal = self.normal.normalized();
+        let tangent = perpendicular(normal) * EXTENT;
+        let bitangent = normal.cross(tangent);
+        let vertices = [
+            tangent + bitangent,
+            tangent - bitangent,
+            -tangent - bitangent,
+            -tangent + bitangent,
+        ]
+        .map(|v| scale_point(v, Vector3::ONE));
+        trimesh_debug_mesh(&vertices, &[[0, 2, 1], [0, 3, 2]])
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DPolylineCollider,
+    #[export]
+    points: PackedVector3Array = PackedVector3Array::new(),
+);
+impl R3DCollider for R3DPolylineCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        if self.points.len() < 2 {
+            return None;
+        }
+
+        let vertices = self
+            .points
+            .as_slice()
+            .iter()
+            .map(|v| scale_point(*v, scale))
+            .collect::<Vec<_>>();
+        Some(SharedShape::polyline(vertices, None))
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        lines_debug_mesh(self.points.as_slice(), true)
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
 }
 
 collider!(
+    /// Collides with the triangles of a mesh. Parry 0.13 can't fix internal
+    /// edges yet, so objects sliding across shared edges may catch on them.
     R3DMeshCollider,
     #[export]
     mesh: Gd<Mesh> = Mesh::new_gd(),
+    #[export]
+    surfaces: PackedInt32Array = PackedInt32Array::new(),
+    #[export]
+    merge_duplicate_vertices: bool = false,
+    #[export]
+    delete_degenerate_triangles: bool = false,
+    #[export]
+    oriented: bool = false,
 );
+impl R3DMeshCollider {
+    fn trimesh_flags(&self) -> TriMeshFlags {
+        let mut flags = TriMeshFlags::empty();
+        flags.set(
+            TriMeshFlags::MERGE_DUPLICATE_VERTICES,
+            self.merge_duplicate_vertices,
+        );
+        flags.set(
+            TriMeshFlags::DELETE_DEGENERATE_TRIANGLES,
+            self.delete_degenerate_triangles,
+        );
+        flags.set(TriMeshFlags::ORIENTED, self.oriented);
+        flags
+    }
+}
 impl R3DCollider for R3DMeshCollider {
-    fn get_shape(&self, scale: f32) -> Option<SharedShape> {
-        if self.mesh.get_surface_count() == 0 {
-            return None;
-        }
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        trimesh_shape(
+            &self.mesh,
+            self.surfaces.as_slice(),
+            scale,
+            self.trimesh_flags(),
+        )
+    }
 
-        let arrays = self.mesh.surface_get_arrays(0);
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        self.mesh.clone()
+    }
 
-        let vertices = arrays
-            .get(ArrayType::VERTEX.to_index())
-            .clone()
-            .to::<PackedVector3Array>()
-            .as_slice()
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DConvexCollider,
+    #[export]
+    mesh: Gd<Mesh> = Mesh::new_gd(),
+    #[export]
+    points: PackedVector3Array = PackedVector3Array::new(),
+);
+impl R3DConvexCollider {
+    /// Explicit points take precedence over the mesh's vertices.
+    fn hull_points(&self, scale: Vector3) -> Vec<Point3<f32>> {
+        let mut points = self.points.as_slice().to_vec();
+        if points.is_empty() {
+            for surface in 0..self.mesh.get_surface_count() {
+                let arrays = self.mesh.surface_get_arrays(surface);
+                let vertices = arrays
+                    .get(ArrayType::VERTEX.to_index())
+                    .try_to::<PackedVector3Array>()
+                    .unwrap_or_default();
+                points.extend_from_slice(vertices.as_slice());
+            }
+        }
+
+        points
             .into_iter()
-            .map(|v| *v * scale)
-            .map(|v| Point3::new(v.x, v.y, v.z))
-            .collect::<Vec<_>>();
+            .map(|v| Point3::new(v.x * scale.x, v.y * scale.y, v.z * scale.z))
+            .collect()
+    }
+}
+impl R3DCollider for R3DConvexCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        SharedShape::convex_hull(&self.hull_points(scale))
+    }
 
-        let mut indices_triplets = Vec::new();
-        let indices_array = arrays
-            .get(ArrayType::INDEX.to_index())
-            .clone()
-            .to::<PackedInt32Array>();
-        let mut indices_iter = indices_array.as_slice().into_iter();
-        while let Some(next) = indices_iter.next() {
-            let i0 = *next as u32;
-            let i1 = *indices_iter.next().unwrap() as u32;
-            let i2 = *indices_iter.next().unwrap() as u32;
-            indices_triplets.push([i0, i1, i2]);
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        hull_debug_mesh(self.get_shape(Vector3::ONE))
+    }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DConvexDecompositionCollider,
+    #[export]
+    mesh: Gd<Mesh> = Mesh::new_gd(),
+    #[export]
+    resolution: u32 = 64,
+    #[export]
+    concavity: f32 = 0.01,
+    #[export]
+    decomposition: Option<Gd<R3DConvexDecomposition>> = None,
+);
+impl R3DConvexDecompositionCollider {
+    /// Runs VHACD on the mesh. A new resource is returned rather than
+    /// rebaking the assigned one, which other colliders may share.
+    pub fn bake_decomposition(&self) -> Option<Gd<R3DConvexDecomposition>> {
+        let (vertices, indices) = mesh_triangles(&self.mesh, &[], Vector3::ONE)?;
+        let mut decomposition = R3DConvexDecomposition::new_gd();
+        decomposition
+            .bind_mut()
+            .bake(&vertices, &indices, self.resolution, self.concavity);
+        Some(decomposition)
+    }
+}
+impl R3DCollider for R3DConvexDecompositionCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        // Only the baked decomposition is used so every peer builds the exact
+        // same compound shape.
+        self.decomposition.as_ref()?.bind().shape(scale)
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        match self.decomposition.as_ref() {
+            Some(decomposition) => {
+                let (vertices, indices) = decomposition.bind().triangles();
+                trimesh_debug_mesh(&vertices, &indices)
+            }
+            None => Mesh::new_gd(),
         }
+    }
 
-        Some(SharedShape::trimesh(vertices, indices_triplets))
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DHeightfieldCollider,
+    #[export]
+    heightmap: Option<Gd<Image>> = None,
+    #[export]
+    heights: PackedFloat32Array = PackedFloat32Array::new(),
+    #[export]
+    resolution: Vector2i = Vector2i::new(2, 2),
+    #[export]
+    size: Vector2 = Vector2::new(10.0, 10.0),
+    #[export]
+    height_scale: f32 = 1.0,
+);
+impl R3DHeightfieldCollider {
+    /// Height samples with rows along Z and columns along X. The heightmap's
+    /// red channel takes precedence over the raw heights.
+    fn height_matrix(&self) -> Option<DMatrix<f32>> {
+        if let Some(heightmap) = self.heightmap.as_ref() {
+            let width = heightmap.get_width();
+            let height = heightmap.get_height();
+            if width < 2 || height < 2 {
+                return None;
+            }
+
+            return Some(DMatrix::from_fn(height as usize, width as usize, |z, x| {
+                heightmap.get_pixel(x as i32, z as i32).r
+            }));
+        }
+
+        let columns = self.resolution.x.max(0) as usize;
+        let rows = self.resolution.y.max(0) as usize;
+        if columns < 2 || rows < 2 || self.heights.len() < columns * rows {
+            return None;
+        }
+
+        let heights = self.heights.as_slice();
+        Some(DMatrix::from_fn(rows, columns, |z, x| {
+            heights[z * columns + x]
+        }))
+    }
+}
+impl R3DCollider for R3DHeightfieldCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        Some(SharedShape::heightfield(
+            self.height_matrix()?,
+            vector![
+                self.size.x * scale.x,
+                self.height_scale * scale.y,
+                self.size.y * scale.z
+            ],
+        ))
     }
 
     fn get_debug_mesh(&self) -> Gd<Mesh> {
-        self.mesh.clone()
+        match self
+            .get_shape(Vector3::ONE)
+            .as_ref()
+            .and_then(|shape| shape.as_heightfield())
+        {
+            Some(heightfield) => {
+                let (vertices, indices) = heightfield.to_trimesh();
+                trimesh_debug_mesh(&vertices, &indices)
+            }
+            None => Mesh::new_gd(),
+        }
     }
+
+    fn is_scale_exact(&self, _scale: Vector3) -> bool {
+        true
+    }
+}
+
+collider!(
+    R3DShapeCollider,
+    #[export]
+    shape: Option<Gd<R3DShape>> = None,
+);
+impl R3DShapeCollider {
+    /// Rebuilds this collider whenever the shared shape is edited.
+    fn watch_shape(&mut self, watch: bool) {
+        let Some(mut shape) = self.shape.clone() else {
+            return;
+        };
+        let callable = Callable::from_object_method(&self.to_gd(), "refresh_shape");
+        let connected = shape.is_connected("changed".into(), callable.clone());
+        if watch && !connected {
+            shape.connect("changed".into(), callable);
+        } else if !watch && connected {
+            shape.disconnect("changed".into(), callable);
+        }
+    }
+}
+impl R3DCollider for R3DShapeCollider {
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        self.shape.as_ref()?.bind().shared_shape(scale)
+    }
+
+    fn get_debug_mesh(&self) -> Gd<Mesh> {
+        self.shape
+            .as_ref()
+            .map(|shape| shape.bind().debug_mesh())
+            .unwrap_or_else(Mesh::new_gd)
+    }
+
+    fn is_scale_exact(&self, scale: Vector3) -> bool {
+        self.shape
+            .as_ref()
+            .map(|shape| shape.bind().is_scale_exact(scale))
+            .unwrap_or(true)
+    }
+
+    fn property_changing(&mut self, property: &str) {
+        if property == "shape" {
+            self.watch_shape(false);
+        }
+    }
+
+    fn property_changed(&mut self, property: &str) {
+        if property == "shape" {
+            self.watch_shape(true);
+        }
+    }
+}
+
+macro_rules! with_collider {
+    ($node:expr, $bind:ident, $collider:ident => $body:expr, $($collider_type:ty),+) => {{
+        let node: Gd<Node3D> = $node.clone();
+        $(
+            if let Ok($collider) = node.clone().try_cast::<$collider_type>() {
+                #[allow(unused_mut)]
+                let mut $collider = $collider;
+                #[allow(unused_mut)]
+                let mut $collider = $collider.$bind();
+                return Some($body);
+            }
+        )+
+        None
+    }};
+    ($node:expr, $bind:ident, $collider:ident => $body:expr) => {
+        with_collider!(
+            $node,
+            $bind,
+            $collider => $body,
+            R3DBallCollider,
+            R3DCapsuleCollider,
+            R3DCuboidCollider,
+            R3DCylinderCollider,
+            R3DConeCollider,
+            R3DRoundCuboidCollider,
+            R3DRoundCylinderCollider,
+            R3DSegmentCollider,
+            R3DTriangleCollider,
+            R3DHalfSpaceCollider,
+            R3DPolylineCollider,
+            R3DMeshCollider,
+            R3DConvexCollider,
+            R3DConvexDecompositionCollider,
+            R3DHeightfieldCollider,
+            R3DShapeCollider
+        )
+    };
+}
+
+/// The shape of any R3D collider node in its own local space.
+pub fn local_shape(node: &Gd<Node3D>) -> Option<SharedShape> {
+    with_collider!(node, bind, collider => collider.get_shape(Vector3::ONE)?)
+}
+
+pub fn gizmo_handles(node: &Gd<Node3D>) -> Option<Vec<GizmoHandle>> {
+    with_collider!(node, bind, collider => collider.gizmo_handles())
+}
+
+pub fn set_gizmo_handle(node: &Gd<Node3D>, handle: usize, distance: f32) -> Option<()> {
+    with_collider!(node, bind_mut, collider => collider.set_gizmo_handle(handle, distance))
 }
@@ -2,14 +2,54 @@ use godot::engine::Mesh;
 use godot::prelude::*;
 use rapier3d::prelude::*;
 
+/// A drag handle offered by the editor gizmo. It sits `distance` along `axis`
+/// from the collider's origin and edits `property`.
+pub struct GizmoHandle {
+    pub property: &'static str,
+    pub axis: Vector3,
+    pub distance: f32,
+}
+
+impl GizmoHandle {
+    pub fn new(property: &'static str, axis: Vector3, distance: f32) -> Self {
+        Self {
+            property,
+            axis,
+            distance,
+        }
+    }
+}
+
 pub trait R3DCollider {
-    fn get_shape(&self, scale: f32) -> Option<SharedShape>;
+    fn get_shape(&self, scale: Vector3) -> Option<SharedShape>;
     fn get_debug_mesh(&self) -> Gd<Mesh>;
+
+    /// Whether `get_shape` represents this scale exactly rather than with a
+    /// convex approximation.
+    fn is_scale_exact(&self, scale: Vector3) -> bool {
+        super::is_uniform_scale(scale)
+    }
+
+    /// Called before one of the collider's own exported properties is set.
+    fn property_changing(&mut self, _property: &str) {}
+
+    /// Called after one of the collider's own exported properties is set,
+    /// before the debug mesh and live shape are refreshed.
+    fn property_changed(&mut self, _property: &str) {}
+
+    /// Drag handles shown by the editor gizmo, in local space.
+    fn gizmo_handles(&self) -> Vec<GizmoHandle> {
+        Vec::new()
+    }
+
+    /// Applies a gizmo handle dragged to `distance` along its axis.
+    fn set_gizmo_handle(&mut self, _handle: usize, _distance: f32) {}
 }
 
 #[macro_export]
 macro_rules! collider {
-    ($type_name:ident, $(#[export] $field_names:ident: $field_types:ty = $field_default:expr),+,) => {
+    ($(#[$attrs:meta])* $type_name:ident, $(#[export] $field_names:ident: $field_types:ty = $field_default:expr),+,) => {
+        $(#[$attrs])*
         #[derive(GodotClass)]
         #[class(tool, base = Node3D)]
         pub struct $type_name {
@@ -18,12 +58,24 @@ macro_rules! collider {
                 #[export]
                 $field_names: $field_types,
             )+
+            /// Re-read this node's transform every tick so animated
+            /// colliders move relative to their body.
+            #[export]
+            sync_transform: bool,
+            #[var(get, set = set_restitution)]
             #[export]
             restitution: f32,
+            #[var(get, set = set_density)]
             #[export]
             density: f32,
+            /// Overrides restitution and density when set.
+            #[var(get, set = set_physics_material)]
+            #[export]
+            physics_material: Option<Gd<$crate::physics_material::R3DPhysicsMaterial>>,
 
             handle: Option<ColliderHandle>,
+            body: Option<Gd<R3DRigidBody>>,
+            world: Option<Gd<$crate::world::R3DWorld>>,
             mesh_instance: Option<Gd<MeshInstance3D>>,
 
             node_3d: Base<Node3D>,
@@ -32,77 +84,217 @@ macro_rules! collider {
         #[godot_api]
         impl $type_name {
             fn setup_debug_mesh(&mut self) {
+                // Scenes saved by older versions contain the preview as a
+                // regular child
+                if let Some(mut legacy) = self.base().get_node_or_null("collider_editor_mesh".into()) {
+                    legacy.queue_free();
+                }
+
+                // Internal and without an owner so it's never saved with the scene
                 let mut mesh_instance = MeshInstance3D::new_alloc();
                 mesh_instance.set_name("collider_editor_mesh".into());
-                self.base_mut().add_child(mesh_instance.clone().upcast::<Node>());
+                self.base_mut()
+                    .add_child_ex(mesh_instance.clone().upcast::<Node>())
+                    .internal(godot::engine::node::InternalMode::FRONT)
+                    .done();
                 self.mesh_instance = Some(mesh_instance);
             }
 
+            #[func]
             fn update_debug_mesh(&mut self) {
-                if let Some(mesh_instance) = self.mesh_instance.clone().as_mut() {
-                    if let Ok(material) = try_load::<godot::engine::StandardMaterial3D>("res://utils/Collider.tres") {
-                        mesh_instance.set_mesh(self.get_debug_mesh());
-                        for surface in 0..mesh_instance.get_surface_override_material_count() {
-                            mesh_instance.set_surface_override_material(surface, material.clone().upcast::<godot::engine::Material>());
+                let Some(mut mesh_instance) = self.mesh_instance.clone() else {
+                    return;
+                };
+
+                let body_type = self.find_body().0.map(|body| body.bind().body_type);
+                let material = $crate::collider::debug_material(body_type);
+                mesh_instance.set_mesh(self.get_debug_mesh());
+                for surface in 0..mesh_instance.get_surface_override_material_count() {
+                    mesh_instance.set_surface_override_material(surface, material.clone());
+                }
+            }
+
+            /// Finds the nearest rigid body ancestor and this collider's
+            /// transform relative to it. Colliders without a body ancestor
+            /// are static and use their global transform.
+            fn find_body(&self) -> (Option<Gd<R3DRigidBody>>, Transform3D) {
+                let mut transform = self.base().get_transform();
+                let mut parent = self.base().get_parent();
+                while let Some(node) = parent {
+                    match node.try_cast::<R3DRigidBody>() {
+                        Ok(body) => return (Some(body), transform),
+                        Err(node) => {
+                            if let Ok(node_3d) = node.clone().try_cast::<Node3D>() {
+                                transform = node_3d.get_transform() * transform;
+                            }
+                            parent = node.get_parent();
                         }
                     }
                 }
+
+                (None, self.base().get_global_transform())
+            }
+
+            /// Lets the owning body re-check whether it has any colliders.
+            fn update_body_warnings(&self) {
+                if let (Some(mut body), _) = self.find_body() {
+                    body.update_configuration_warnings();
+                }
             }
 
             fn register_collider(&mut self) {
-                // TODO: This needs to not panic. Should display this as a warning.
-                let mut body = self.base().get_parent().unwrap().cast::<R3DRigidBody>();
-
-                let scale = self.base().get_scale();
-                let translation = self.base().get_position();
-                let rotation = self.base().get_rotation();
-
-                if scale.x != scale.y || scale.x != scale.z {
-                    panic!("Non-uniform scaling is not supported for colliders");
-                }
-
-                if let Some(shape) = self.get_shape(scale.x) {
-                    self.handle = {
-                        let mut body = body.bind_mut();
-                        body.add_collider(
-                            ColliderBuilder::new(shape)
-                                .translation(vector![translation.x, translation.y, translation.z])
-                                .rotation(vector![rotation.x, rotation.y, rotation.z])
-                                .restitution(self.restitution)
-                                .density(self.density)
-                                .build(),
-                        )
+                let (body, transform) = self.find_body();
+                let scale = transform.basis.scale();
+
+                if !self.is_scale_exact(scale) {
+                    godot_warn!(
+                        "{} is scaled non-uniformly and will be approximated by a convex polyhedron",
+                        self.base().get_path()
+                    );
+                }
+
+                let Some(shape) = self.get_shape(scale) else {
+                    godot_error!("{} did not return a valid shape", self.base().get_path());
+                    return;
+                };
+
+                let mut collider = ColliderBuilder::new(shape)
+                    .position($crate::utils::transform_to_isometry(&transform))
+                    .build();
+                self.apply_material(&mut collider);
+
+                if let Some(mut body) = body {
+                    self.handle = body.bind_mut().add_collider(collider);
+                    self.body = Some(body);
+                } else {
+                    let Some(mut world) = $crate::world::R3DWorld::find(&self.base()) else {
+                        return;
                     };
+                    self.handle = Some(world.bind_mut().add_collider(collider));
+                    self.world = Some(world);
+                }
+            }
+
+            fn update_collider(&self, update: impl FnOnce(&mut Collider)) {
+                let Some(handle) = self.handle else {
+                    return;
+                };
+
+                if let Some(mut body) = self.body.clone() {
+                    body.bind_mut().update_collider(handle, update);
+                } else if let Some(mut world) = self.world.clone() {
+                    if let Some(collider) = world.bind_mut().collider_mut(handle) {
+                        update(collider);
+                    }
+                }
+            }
+
+            fn update_collider_shape(&mut self) {
+                if self.handle.is_none() {
+                    return;
+                }
+
+                let scale = self.find_body().1.basis.scale();
+                if let Some(shape) = self.get_shape(scale) {
+                    self.update_collider(|collider| collider.set_shape(shape));
                 } else {
-                    eprintln!("Collider did not return a valid shape");
+                    godot_error!("{} did not return a valid shape", self.base().get_path());
                 }
             }
 
-            fn unregister_collider(&mut self) {
-                if let Some(mut body_gd) = self
-                    .base()
-                    .get_parent()
-                    .and_then(|p| p.try_cast::<R3DRigidBody>().ok())
-                {
-                    if let Some(handle) = self.handle {
-                        let mut body = body_gd.bind_mut();
-                        body.remove_collider(handle);
+            /// Moves the collider to match this node's current transform
+            /// relative to its body, or in the world for static colliders.
+            #[func]
+            pub fn sync_collider_transform(&mut self) {
+                let position = $crate::utils::transform_to_isometry(&self.find_body().1);
+                self.update_collider(|collider| {
+                    if collider.parent().is_some() {
+                        if collider.position_wrt_parent() != Some(&position) {
+                            collider.set_position_wrt_parent(position);
+                        }
+                    } else if *collider.position() != position {
+                        collider.set_position(position);
                     }
+                });
+            }
+
+            #[func]
+            pub fn networked_preprocess(&mut self) {
+                if self.sync_transform {
+                    self.sync_collider_transform();
+                }
+            }
+
+            fn apply_material(&self, collider: &mut Collider) {
+                if let Some(material) = self.physics_material.as_ref() {
+                    material.bind().apply(collider);
                 } else {
-                    godot_print!("Collider parent is not a rigid body");
+                    collider.set_friction(ColliderBuilder::default_friction());
+                    collider.set_friction_combine_rule(CoefficientCombineRule::default());
+                    collider.set_restitution(self.restitution);
+                    collider.set_restitution_combine_rule(CoefficientCombineRule::default());
+                    collider.set_density(self.density);
+                }
+            }
+
+            fn update_collider_material(&mut self) {
+                self.update_collider(|collider| self.apply_material(collider));
+            }
+
+            #[func]
+            pub fn set_restitution(&mut self, restitution: f32) {
+                self.restitution = restitution;
+                self.update_collider_material();
+            }
+
+            #[func]
+            pub fn set_density(&mut self, density: f32) {
+                self.density = density;
+                self.update_collider_material();
+            }
+
+            #[func]
+            pub fn set_physics_material(
+                &mut self,
+                physics_material: Option<Gd<$crate::physics_material::R3DPhysicsMaterial>>,
+            ) {
+                self.physics_material = physics_material;
+                self.update_collider_material();
+            }
+
+            fn unregister_collider(&mut self) {
+                let Some(handle) = self.handle.take() else {
+                    return;
+                };
+
+                if let Some(mut body) = self.body.take() {
+                    body.bind_mut().remove_collider(handle);
+                } else if let Some(mut world) = self.world.take() {
+                    world.bind_mut().remove_collider(handle);
                 }
             }
 
             $(
                 #[func]
                 pub fn $field_names(&mut self, $field_names: $field_types) {
+                    self.property_changing(stringify!($field_names));
                     self.$field_names = $field_names;
-                    let editor = Engine::singleton().is_editor_hint();
-                    if editor {
-                        self.update_debug_mesh();
-                    }
+                    self.property_changed(stringify!($field_names));
+                    self.refresh_shape();
                 }
             )+
+
+            /// Rebuilds the debug mesh and live shape after the shape's
+            /// properties or resources changed.
+            #[func]
+            pub fn refresh_shape(&mut self) {
+                if Engine::singleton().is_editor_hint() {
+                    self.update_debug_mesh();
+                    self.base_mut().update_configuration_warnings();
+                    self.base_mut().update_gizmos();
+                }
+                self.update_collider_shape();
+            }
         }
 
         #[godot_api]
@@ -112,9 +304,13 @@ macro_rules! collider {
                     $(
                         $field_names: $field_default,
                     )+
+                    sync_transform: false,
                     restitution: 0.0,
                     density: 1.0,
+                    physics_material: None,
                     handle: None,
+                    body: None,
+                    world: None,
                     mesh_instance: None,
                     node_3d,
                 }
@@ -124,16 +320,42 @@ macro_rules! collider {
                 if Engine::singleton().is_editor_hint() {
                     self.setup_debug_mesh();
                     self.update_debug_mesh();
+                } else if self.sync_transform {
+                    self.base_mut().add_to_group("networked".into());
                 }
             }
 
             fn enter_tree(&mut self) {
-                self.register_collider();
+                if Engine::singleton().is_editor_hint() {
+                    self.update_body_warnings();
+                    // The body, and with it the preview color, may have changed
+                    self.update_debug_mesh();
+                } else {
+                    self.register_collider();
+                }
             }
 
             fn exit_tree(&mut self) {
+                if Engine::singleton().is_editor_hint() {
+                    self.update_body_warnings();
+                }
                 self.unregister_collider();
             }
+
+            fn get_configuration_warnings(&self) -> PackedStringArray {
+                let mut warnings = PackedStringArray::new();
+                let scale = self.find_body().1.basis.scale();
+                if !self.is_scale_exact(scale) {
+                    warnings.push(
+                        "This collider is scaled non-uniformly and will be approximated by a convex polyhedron."
+                            .into(),
+                    );
+                }
+                if self.get_shape(scale).is_none() {
+                    warnings.push("This collider does not produce a valid shape with its current properties.".into());
+                }
+                warnings
+            }
         }
     };
 }
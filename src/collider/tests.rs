@@ -0,0 +1,30 @@
+use godot::prelude::*;
+
+use super::{ball_shape, is_uniform_scale, is_uniform_xz_scale};
+
+/// A uniform scale of 2 under a rotated parent, as `find_body` composes it.
+fn rotated_uniform_scale() -> Vector3 {
+    let parent = Basis::from_axis_angle(Vector3::new(1.0, 2.0, 3.0).normalized(), 0.7);
+    (parent * Basis::from_scale(Vector3::new(2.0, 2.0, 2.0))).scale()
+}
+
+#[test]
+fn rotated_uniform_scale_is_uniform() {
+    let scale = rotated_uniform_scale();
+    assert!(is_uniform_scale(scale));
+    assert!(is_uniform_xz_scale(scale));
+}
+
+#[test]
+fn rotated_uniformly_scaled_ball_stays_a_ball() {
+    let shape = ball_shape(0.5, rotated_uniform_scale()).unwrap();
+    let ball = shape.as_ball().expect("built as a convex approximation");
+    assert!((ball.radius - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn non_uniform_scale_is_detected() {
+    assert!(!is_uniform_scale(Vector3::new(1.0, 1.1, 1.0)));
+    assert!(!is_uniform_xz_scale(Vector3::new(1.0, 1.0, 1.1)));
+    assert!(is_uniform_xz_scale(Vector3::new(1.0, 3.0, 1.0)));
+}
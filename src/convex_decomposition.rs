@@ -0,0 +1,117 @@
+use godot::prelude::*;
+use rapier3d::{
+    na::Point3,
+    parry::transformation::vhacd::{VHACDParameters, VHACD},
+    prelude::*,
+};
+
+/// Convex parts of a concave mesh, computed once in the editor and saved with
+/// the scene so that peers never run VHACD themselves.
+#[derive(GodotClass)]
+#[class(tool, base = Resource)]
+pub struct R3DConvexDecomposition {
+    #[export]
+    pub part_vertices: Array<PackedVector3Array>,
+    #[export]
+    pub part_indices: Array<PackedInt32Array>,
+
+    resource: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for R3DConvexDecomposition {
+    fn init(resource: Base<Resource>) -> Self {
+        Self {
+            part_vertices: Array::new(),
+            part_indices: Array::new(),
+            resource,
+        }
+    }
+}
+
+impl R3DConvexDecomposition {
+    pub fn bake(
+        &mut self,
+        vertices: &[Point3<f32>],
+        indices: &[[u32; 3]],
+        resolution: u32,
+        concavity: f32,
+    ) {
+        let params = VHACDParameters {
+            resolution,
+            concavity,
+            ..VHACDParameters::default()
+        };
+        let decomposition = VHACD::decompose(&params, vertices, indices, true);
+
+        self.part_vertices.clear();
+        self.part_indices.clear();
+        for (vertices, indices) in decomposition.compute_exact_convex_hulls(vertices, indices) {
+            self.part_vertices.push(
+                vertices
+                    .iter()
+                    .map(|v| Vector3::new(v.x, v.y, v.z))
+                    .collect::<PackedVector3Array>(),
+            );
+            self.part_indices.push(
+                indices
+                    .iter()
+                    .flatten()
+                    .map(|i| *i as i32)
+                    .collect::<PackedInt32Array>(),
+            );
+        }
+
+        self.base_mut().emit_changed();
+    }
+
+    /// Iterates over the parts as triangle meshes with the given scale applied.
+    fn parts(&self, scale: Vector3) -> impl Iterator<Item = (Vec<Point3<f32>>, Vec<[u32; 3]>)> {
+        self.part_vertices
+            .iter_shared()
+            .zip(self.part_indices.iter_shared())
+            .map(move |(vertices, indices)| {
+                let vertices = vertices
+                    .as_slice()
+                    .iter()
+                    .map(|v| Point3::new(v.x * scale.x, v.y * scale.y, v.z * scale.z))
+                    .collect::<Vec<_>>();
+                let indices = indices
+                    .as_slice()
+                    .chunks_exact(3)
+                    .map(|i| [i[0] as u32, i[1] as u32, i[2] as u32])
+                    .collect::<Vec<_>>();
+                (vertices, indices)
+            })
+    }
+
+    pub fn shape(&self, scale: Vector3) -> Option<SharedShape> {
+        let parts = self
+            .parts(scale)
+            .filter_map(|(vertices, indices)| SharedShape::convex_mesh(vertices, &indices))
+            .map(|part| (Isometry::identity(), part))
+            .collect::<Vec<_>>();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(SharedShape::compound(parts))
+        }
+    }
+
+    /// All parts merged into one triangle list for debug rendering.
+    pub fn triangles(&self) -> (Vec<Point3<f32>>, Vec<[u32; 3]>) {
+        let mut all_vertices = Vec::new();
+        let mut all_indices = Vec::new();
+        for (vertices, indices) in self.parts(Vector3::ONE) {
+            let offset = all_vertices.len() as u32;
+            all_vertices.extend(vertices);
+            all_indices.extend(
+                indices
+                    .into_iter()
+                    .map(|[a, b, c]| [a + offset, b + offset, c + offset]),
+            );
+        }
+        (all_vertices, all_indices)
+    }
+}
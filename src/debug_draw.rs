@@ -0,0 +1,242 @@
+use godot::{
+    engine::{
+        base_material_3d::{Flags, ShadingMode, Transparency},
+        mesh::PrimitiveType,
+        IMeshInstance3D, ImmediateMesh, Material, MeshInstance3D, StandardMaterial3D,
+    },
+    prelude::*,
+};
+use rapier3d::{
+    pipeline::{
+        DebugColor, DebugRenderBackend, DebugRenderMode, DebugRenderObject, DebugRenderPipeline,
+        DebugRenderStyle,
+    },
+    prelude::*,
+};
+
+use crate::world::R3DWorld;
+
+/// Draws what the physics world sees while the game is running. Lines are
+/// taken from the current tick of the R3DWorld every frame, so rollbacks
+/// show up immediately.
+#[derive(GodotClass)]
+#[class(base = MeshInstance3D)]
+pub struct R3DDebugDraw {
+    #[export]
+    enabled: bool,
+    #[export]
+    draw_colliders: bool,
+    #[export]
+    draw_aabbs: bool,
+    #[export]
+    draw_contacts: bool,
+    #[export]
+    draw_joints: bool,
+    #[export]
+    draw_body_axes: bool,
+    /// Draw on top of the scene instead of being hidden by geometry.
+    #[export]
+    always_on_top: bool,
+
+    #[export]
+    dynamic_color: Color,
+    #[export]
+    kinematic_color: Color,
+    #[export]
+    fixed_color: Color,
+    /// Used for colliders that aren't attached to a body.
+    #[export]
+    static_color: Color,
+    #[export]
+    aabb_color: Color,
+    #[export]
+    contact_depth_color: Color,
+    #[export]
+    contact_normal_color: Color,
+    #[export]
+    contact_normal_length: f32,
+    #[export]
+    joint_anchor_color: Color,
+    #[export]
+    joint_separation_color: Color,
+    /// Lightness multiplier applied to sleeping bodies.
+    #[export(range = (0.0, 1.0))]
+    sleeping_lightness: f32,
+
+    pipeline: DebugRenderPipeline,
+    mesh: Gd<ImmediateMesh>,
+    material: Gd<StandardMaterial3D>,
+    world: Option<Gd<R3DWorld>>,
+
+    base: Base<MeshInstance3D>,
+}
+
+#[godot_api]
+impl IMeshInstance3D for R3DDebugDraw {
+    fn init(base: Base<MeshInstance3D>) -> Self {
+        let style = DebugRenderStyle::default();
+
+        let mut material = StandardMaterial3D::new_gd();
+        material.set_shading_mode(ShadingMode::UNSHADED);
+        material.set_flag(Flags::ALBEDO_FROM_VERTEX_COLOR, true);
+        material.set_transparency(Transparency::ALPHA);
+
+        Self {
+            enabled: true,
+            draw_colliders: true,
+            draw_aabbs: false,
+            draw_contacts: true,
+            draw_joints: true,
+            draw_body_axes: false,
+            always_on_top: false,
+
+            dynamic_color: from_hsla(style.collider_dynamic_color),
+            kinematic_color: from_hsla(style.collider_kinematic_color),
+            fixed_color: from_hsla(style.collider_fixed_color),
+            static_color: from_hsla(style.collider_parentless_color),
+            aabb_color: from_hsla(style.collider_aabb_color),
+            contact_depth_color: from_hsla(style.contact_depth_color),
+            contact_normal_color: from_hsla(style.contact_normal_color),
+            contact_normal_length: style.contact_normal_length,
+            joint_anchor_color: from_hsla(style.impulse_joint_anchor_color),
+            joint_separation_color: from_hsla(style.impulse_joint_separation_color),
+            sleeping_lightness: style.sleep_color_multiplier[2],
+
+            pipeline: DebugRenderPipeline::new(style, DebugRenderMode::empty()),
+            mesh: ImmediateMesh::new_gd(),
+            material,
+            world: None,
+
+            base,
+        }
+    }
+
+    fn ready(&mut self) {
+        let mesh = self.mesh.clone();
+        let mut mesh_instance = self.base_mut();
+        mesh_instance.set_mesh(mesh.upcast());
+        // Lines are in world space
+        mesh_instance.set_as_top_level(true);
+        mesh_instance.set_global_transform(Transform3D::IDENTITY);
+
+        self.world = R3DWorld::find(&self.base());
+    }
+
+    fn process(&mut self, _delta: f64) {
+        self.mesh.clear_surfaces();
+        if !self.enabled {
+            return;
+        }
+        let Some(world) = self.world.clone() else {
+            return;
+        };
+
+        self.pipeline.mode = self.mode();
+        self.pipeline.style = self.style();
+        self.material
+            .set_flag(Flags::DISABLE_DEPTH_TEST, self.always_on_top);
+
+        let mut lines = DebugLines::default();
+        world.bind().debug_render(&mut self.pipeline, &mut lines);
+        if lines.0.is_empty() {
+            // ImmediateMesh errors on empty surfaces
+            return;
+        }
+
+        self.mesh
+            .surface_begin_ex(PrimitiveType::LINES)
+            .material(self.material.clone().upcast::<Material>())
+            .done();
+        for (a, b, color) in lines.0 {
+            self.mesh.surface_set_color(color);
+            self.mesh.surface_add_vertex(Vector3::new(a.x, a.y, a.z));
+            self.mesh.surface_add_vertex(Vector3::new(b.x, b.y, b.z));
+        }
+        self.mesh.surface_end();
+    }
+}
+
+impl R3DDebugDraw {
+    fn mode(&self) -> DebugRenderMode {
+        let mut mode = DebugRenderMode::empty();
+        mode.set(DebugRenderMode::COLLIDER_SHAPES, self.draw_colliders);
+        mode.set(DebugRenderMode::COLLIDER_AABBS, self.draw_aabbs);
+        mode.set(DebugRenderMode::CONTACTS, self.draw_contacts);
+        mode.set(DebugRenderMode::JOINTS, self.draw_joints);
+        mode.set(DebugRenderMode::RIGID_BODY_AXES, self.draw_body_axes);
+        mode
+    }
+
+    fn style(&self) -> DebugRenderStyle {
+        let joint_anchor_color = to_hsla(self.joint_anchor_color);
+        let joint_separation_color = to_hsla(self.joint_separation_color);
+        DebugRenderStyle {
+            collider_dynamic_color: to_hsla(self.dynamic_color),
+            collider_kinematic_color: to_hsla(self.kinematic_color),
+            collider_fixed_color: to_hsla(self.fixed_color),
+            collider_parentless_color: to_hsla(self.static_color),
+            collider_aabb_color: to_hsla(self.aabb_color),
+            contact_depth_color: to_hsla(self.contact_depth_color),
+            contact_normal_color: to_hsla(self.contact_normal_color),
+            contact_normal_length: self.contact_normal_length,
+            impulse_joint_anchor_color: joint_anchor_color,
+            impulse_joint_separation_color: joint_separation_color,
+            multibody_joint_anchor_color: joint_anchor_color,
+            multibody_joint_separation_color: joint_separation_color,
+            sleep_color_multiplier: [1.0, 1.0, self.sleeping_lightness, 1.0],
+            ..DebugRenderStyle::default()
+        }
+    }
+}
+
+#[derive(Default)]
+struct DebugLines(Vec<(Point<Real>, Point<Real>, Color)>);
+
+impl DebugRenderBackend for DebugLines {
+    fn draw_line(
+        &mut self,
+        _object: DebugRenderObject,
+        a: Point<Real>,
+        b: Point<Real>,
+        color: DebugColor,
+    ) {
+        self.0.push((a, b, from_hsla(color)));
+    }
+}
+
+/// Rapier colors are HSLA with the hue in degrees.
+fn to_hsla(color: Color) -> DebugColor {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta == 0.0 {
+        return [0.0, 0.0, lightness, color.a];
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == color.r {
+        ((color.g - color.b) / delta).rem_euclid(6.0)
+    } else if max == color.g {
+        (color.b - color.r) / delta + 2.0
+    } else {
+        (color.r - color.g) / delta + 4.0
+    };
+    [hue * 60.0, saturation, lightness, color.a]
+}
+
+fn from_hsla([hue, saturation, lightness, alpha]: DebugColor) -> Color {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    Color::from_rgba(r + m, g + m, b + m, alpha)
+}
@@ -0,0 +1,219 @@
+use godot::{
+    engine::{
+        Camera3D, EditorNode3DGizmo, EditorNode3DGizmoPlugin, EditorUndoRedoManager, Geometry3D,
+        IEditorNode3DGizmoPlugin, Material,
+    },
+    prelude::*,
+};
+use rapier3d::{
+    pipeline::{
+        DebugColor, DebugRenderBackend, DebugRenderMode, DebugRenderObject, DebugRenderPipeline,
+        DebugRenderStyle,
+    },
+    prelude::*,
+};
+
+use crate::collider;
+
+const LINES_MATERIAL: &str = "r3d_collider";
+const HANDLES_MATERIAL: &str = "r3d_collider_handles";
+/// Length of the segments used to find where a handle is dragged to.
+const HANDLE_RAY_LENGTH: f32 = 4096.0;
+
+/// Draws wireframes for every R3D collider in the editor and offers drag
+/// handles for their dimensions.
+#[derive(GodotClass)]
+#[class(tool, base = EditorNode3DGizmoPlugin)]
+pub struct R3DColliderGizmoPlugin {
+    undo_redo: Option<Gd<EditorUndoRedoManager>>,
+
+    base: Base<EditorNode3DGizmoPlugin>,
+}
+
+#[godot_api]
+impl IEditorNode3DGizmoPlugin for R3DColliderGizmoPlugin {
+    fn init(base: Base<EditorNode3DGizmoPlugin>) -> Self {
+        Self {
+            undo_redo: None,
+            base,
+        }
+    }
+
+    fn get_gizmo_name(&self) -> GString {
+        "R3DCollider".into()
+    }
+
+    fn has_gizmo(&self, for_node_3d: Gd<Node3D>) -> bool {
+        collider::gizmo_handles(&for_node_3d).is_some()
+    }
+
+    fn redraw(&mut self, gizmo: Gd<EditorNode3DGizmo>) {
+        let mut gizmo = gizmo;
+        gizmo.clear();
+        let Some(node) = gizmo.get_node_3d() else {
+            return;
+        };
+
+        let lines_material = self.material(LINES_MATERIAL, &gizmo);
+        if let (Some(lines), Some(material)) = (shape_lines(&node), lines_material) {
+            gizmo.add_lines(lines, material);
+        }
+
+        let handles = collider::gizmo_handles(&node).unwrap_or_default();
+        let handles_material = self.material(HANDLES_MATERIAL, &gizmo);
+        if let (false, Some(material)) = (handles.is_empty(), handles_material) {
+            let mut positions = PackedVector3Array::new();
+            for handle in handles.iter() {
+                positions.push(handle.axis * handle.distance);
+            }
+            gizmo.add_handles(positions, material, PackedInt32Array::new());
+        }
+    }
+
+    fn get_handle_name(
+        &self,
+        gizmo: Gd<EditorNode3DGizmo>,
+        handle_id: i32,
+        _secondary: bool,
+    ) -> GString {
+        handle_property(&gizmo, handle_id)
+            .map(GString::from)
+            .unwrap_or_default()
+    }
+
+    fn get_handle_value(
+        &self,
+        gizmo: Gd<EditorNode3DGizmo>,
+        handle_id: i32,
+        _secondary: bool,
+    ) -> Variant {
+        match (gizmo.get_node_3d(), handle_property(&gizmo, handle_id)) {
+            (Some(node), Some(property)) => node.get(property.into()),
+            _ => Variant::nil(),
+        }
+    }
+
+    fn set_handle(
+        &mut self,
+        gizmo: Gd<EditorNode3DGizmo>,
+        handle_id: i32,
+        _secondary: bool,
+        camera: Gd<Camera3D>,
+        screen_pos: Vector2,
+    ) {
+        let Some(mut node) = gizmo.get_node_3d() else {
+            return;
+        };
+        let Ok(handle_index) = usize::try_from(handle_id) else {
+            return;
+        };
+        let handles = collider::gizmo_handles(&node).unwrap_or_default();
+        let Some(handle) = handles.get(handle_index) else {
+            return;
+        };
+
+        // Find the point on the handle's axis closest to the mouse ray, in
+        // the collider's local space
+        let to_local = node.get_global_transform().affine_inverse();
+        let ray_origin = camera.project_ray_origin(screen_pos);
+        let ray_end = ray_origin + camera.project_ray_normal(screen_pos) * HANDLE_RAY_LENGTH;
+        let closest = Geometry3D::singleton().get_closest_points_between_segments(
+            -handle.axis * HANDLE_RAY_LENGTH,
+            handle.axis * HANDLE_RAY_LENGTH,
+            to_local * ray_origin,
+            to_local * ray_end,
+        );
+        let Some(closest) = closest.as_slice().first() else {
+            return;
+        };
+        let distance = closest.dot(handle.axis).max(0.001);
+
+        collider::set_gizmo_handle(&node, handle_index, distance);
+        node.update_gizmos();
+    }
+
+    fn commit_handle(
+        &mut self,
+        gizmo: Gd<EditorNode3DGizmo>,
+        handle_id: i32,
+        _secondary: bool,
+        restore: Variant,
+        cancel: bool,
+    ) {
+        let (Some(mut node), Some(property)) =
+            (gizmo.get_node_3d(), handle_property(&gizmo, handle_id))
+        else {
+            return;
+        };
+
+        if cancel {
+            node.set(property.into(), restore);
+            return;
+        }
+
+        let Some(undo_redo) = self.undo_redo.as_mut() else {
+            return;
+        };
+        let object = node.clone().upcast::<Object>();
+        undo_redo.create_action(format!("Change Collider {property}").into());
+        undo_redo.add_do_property(object.clone(), property.into(), node.get(property.into()));
+        undo_redo.add_undo_property(object, property.into(), restore);
+        undo_redo.commit_action();
+    }
+}
+
+impl R3DColliderGizmoPlugin {
+    /// Creates the gizmo materials. Called by the editor plugin before the
+    /// gizmo plugin is added.
+    pub fn setup(&mut self, undo_redo: Option<Gd<EditorUndoRedoManager>>) {
+        self.undo_redo = undo_redo;
+        let mut plugin = self.base_mut();
+        plugin.create_material(LINES_MATERIAL.into(), Color::from_rgba(0.5, 0.7, 1.0, 1.0));
+        plugin.create_handle_material(HANDLES_MATERIAL.into());
+    }
+
+    fn material(&mut self, name: &str, gizmo: &Gd<EditorNode3DGizmo>) -> Option<Gd<Material>> {
+        self.base_mut()
+            .get_material_ex(name.into())
+            .gizmo(gizmo.clone())
+            .done()
+            .map(|material| material.upcast())
+    }
+}
+
+fn handle_property(gizmo: &Gd<EditorNode3DGizmo>, handle_id: i32) -> Option<&'static str> {
+    let handles = collider::gizmo_handles(&gizmo.get_node_3d()?)?;
+    let handle = handles.get(usize::try_from(handle_id).ok()?)?;
+    Some(handle.property)
+}
+
+/// Wireframe of the collider's shape, drawn the same way as R3DDebugDraw.
+fn shape_lines(node: &Gd<Node3D>) -> Option<PackedVector3Array> {
+    let shape = collider::local_shape(node)?;
+    let mut colliders = ColliderSet::new();
+    colliders.insert(ColliderBuilder::new(shape).build());
+
+    let mut lines = GizmoLines::default();
+    DebugRenderPipeline::new(
+        DebugRenderStyle::default(),
+        DebugRenderMode::COLLIDER_SHAPES,
+    )
+    .render_colliders(&mut lines, &RigidBodySet::new(), &colliders);
+    Some(lines.0)
+}
+
+#[derive(Default)]
+struct GizmoLines(PackedVector3Array);
+
+impl DebugRenderBackend for GizmoLines {
+    fn draw_line(
+        &mut self,
+        _object: DebugRenderObject,
+        a: Point<Real>,
+        b: Point<Real>,
+        _color: DebugColor,
+    ) {
+        self.0.push(Vector3::new(a.x, a.y, a.z));
+        self.0.push(Vector3::new(b.x, b.y, b.z));
+    }
+}
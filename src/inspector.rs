@@ -0,0 +1,75 @@
+use godot::{
+    engine::{Button, EditorInspectorPlugin, EditorUndoRedoManager, IEditorInspectorPlugin},
+    prelude::*,
+};
+
+use crate::collider::R3DConvexDecompositionCollider;
+
+/// Adds a bake button to R3DConvexDecompositionCollider in the inspector.
+/// VHACD is slow on large meshes, so it only runs when asked to.
+#[derive(GodotClass)]
+#[class(tool, base = EditorInspectorPlugin)]
+pub struct R3DColliderInspectorPlugin {
+    undo_redo: Option<Gd<EditorUndoRedoManager>>,
+    edited: Option<Gd<R3DConvexDecompositionCollider>>,
+
+    base: Base<EditorInspectorPlugin>,
+}
+
+#[godot_api]
+impl IEditorInspectorPlugin for R3DColliderInspectorPlugin {
+    fn init(base: Base<EditorInspectorPlugin>) -> Self {
+        Self {
+            undo_redo: None,
+            edited: None,
+            base,
+        }
+    }
+
+    fn can_handle(&self, object: Gd<Object>) -> bool {
+        object.try_cast::<R3DConvexDecompositionCollider>().is_ok()
+    }
+
+    fn parse_begin(&mut self, object: Gd<Object>) {
+        self.edited = object.try_cast::<R3DConvexDecompositionCollider>().ok();
+
+        let mut button = Button::new_alloc();
+        button.set_text("Bake Convex Decomposition".into());
+        button.connect(
+            "pressed".into(),
+            Callable::from_object_method(&self.to_gd(), "bake_decomposition"),
+        );
+        self.base_mut().add_custom_control(button.upcast());
+    }
+}
+
+#[godot_api]
+impl R3DColliderInspectorPlugin {
+    /// Called by the editor plugin before the inspector plugin is added.
+    pub fn setup(&mut self, undo_redo: Option<Gd<EditorUndoRedoManager>>) {
+        self.undo_redo = undo_redo;
+    }
+
+    /// Replaces the edited collider's decomposition with a newly baked one.
+    #[func]
+    fn bake_decomposition(&mut self) {
+        let Some(mut collider) = self.edited.clone() else {
+            return;
+        };
+        let Some(decomposition) = collider.bind().bake_decomposition() else {
+            godot_warn!("{} has no mesh triangles to decompose", collider.get_path());
+            return;
+        };
+
+        let property = StringName::from("decomposition");
+        let Some(undo_redo) = self.undo_redo.as_mut() else {
+            collider.set(property, decomposition.to_variant());
+            return;
+        };
+        let object = collider.clone().upcast::<Object>();
+        undo_redo.create_action("Bake Convex Decomposition".into());
+        undo_redo.add_do_property(object.clone(), property.clone(), decomposition.to_variant());
+        undo_redo.add_undo_property(object, property.clone(), collider.get(property));
+        undo_redo.commit_action();
+    }
+}
@@ -1,16 +1,31 @@
 pub mod collider;
+pub mod convex_decomposition;
+pub mod debug_draw;
+pub mod gizmo;
+pub mod inspector;
+pub mod physics_material;
+pub mod ragdoll;
 pub mod raycast_result;
 pub mod rigid_body;
+pub mod shape;
+pub mod simulation;
 pub mod utils;
+pub mod vehicle;
 pub mod world;
 
 use std::path::PathBuf;
 
 use godot::{
-    engine::{EditorPlugin, IEditorPlugin, ProjectSettings, ResourceSaver},
+    engine::{
+        global::PropertyHint, EditorPlugin, IEditorPlugin, ProjectSettings, ResourceLoader,
+        ResourceSaver,
+    },
+    obj::EngineEnum,
     prelude::*,
 };
 
+use gizmo::R3DColliderGizmoPlugin;
+use inspector::R3DColliderInspectorPlugin;
 use world::R3DWorld;
 
 struct GdRapier3d;
@@ -21,39 +36,185 @@ unsafe impl ExtensionLibrary for GdRapier3d {}
 #[derive(GodotClass)]
 #[class(tool, editor_plugin, base=EditorPlugin)]
 struct GdRapier3dEditorPlugin {
+    gizmo_plugin: Option<Gd<R3DColliderGizmoPlugin>>,
+    inspector_plugin: Option<Gd<R3DColliderInspectorPlugin>>,
+
     base: Base<EditorPlugin>,
 }
 
 #[godot_api]
 impl IEditorPlugin for GdRapier3dEditorPlugin {
     fn init(base: Base<EditorPlugin>) -> Self {
-        GdRapier3dEditorPlugin { base }
+        GdRapier3dEditorPlugin {
+            gizmo_plugin: None,
+            inspector_plugin: None,
+            base,
+        }
     }
 
     fn enter_tree(&mut self) {
-        let project_settings = ProjectSettings::singleton();
-        let directory_string: String = project_settings
-            .globalize_path("res://autoloads".into())
-            .into();
-        let directory_path = PathBuf::from(directory_string);
+        let mut gizmo_plugin = R3DColliderGizmoPlugin::new_gd();
+        let undo_redo = self.base_mut().get_undo_redo();
+        gizmo_plugin.bind_mut().setup(undo_redo);
+        self.base_mut()
+            .add_node_3d_gizmo_plugin(gizmo_plugin.clone().upcast());
+        self.gizmo_plugin = Some(gizmo_plugin);
+
+        let mut inspector_plugin = R3DColliderInspectorPlugin::new_gd();
+        let undo_redo = self.base_mut().get_undo_redo();
+        inspector_plugin.bind_mut().setup(undo_redo);
+        self.base_mut()
+            .add_inspector_plugin(inspector_plugin.clone().upcast());
+        self.inspector_plugin = Some(inspector_plugin);
+
+        add_project_setting(
+            collider::DEBUG_MATERIAL_SETTING,
+            GString::new().to_variant(),
+            PropertyHint::FILE,
+            "*.tres,*.res,*.material",
+        );
+
+        add_project_setting(
+            world::AUTOLOAD_ENABLED_SETTING,
+            true.to_variant(),
+            PropertyHint::NONE,
+            "",
+        );
+        add_project_setting(
+            world::AUTOLOAD_NAME_SETTING,
+            GString::from(world::DEFAULT_AUTOLOAD_NAME).to_variant(),
+            PropertyHint::NONE,
+            "",
+        );
+        add_project_setting(
+            world::AUTOLOAD_PATH_SETTING,
+            GString::from(world::DEFAULT_AUTOLOAD_PATH).to_variant(),
+            PropertyHint::FILE,
+            "*.tscn",
+        );
+
+        if autoload_enabled() {
+            self.install_autoload();
+        } else {
+            self.uninstall_autoload();
+        }
+    }
 
-        std::fs::create_dir_all(directory_path).expect("Couldn't create autoloads directory");
+    fn exit_tree(&mut self) {
+        if let Some(gizmo_plugin) = self.gizmo_plugin.take() {
+            self.base_mut()
+                .remove_node_3d_gizmo_plugin(gizmo_plugin.upcast());
+        }
+        if let Some(inspector_plugin) = self.inspector_plugin.take() {
+            self.base_mut()
+                .remove_inspector_plugin(inspector_plugin.upcast());
+        }
+
+        collider::clear_debug_materials();
+
+        // This also runs whenever the editor closes, so the autoload is only
+        // removed once the project has opted out of it
+        if !autoload_enabled() {
+            self.uninstall_autoload();
+        }
+    }
+}
+
+impl GdRapier3dEditorPlugin {
+    /// Adds the R3DWorld autoload unless the project already has one with
+    /// the configured name. An existing scene is never overwritten.
+    fn install_autoload(&mut self) {
+        let name = R3DWorld::autoload_name();
+        let path = autoload_path();
+        let project_settings = ProjectSettings::singleton();
+        if project_settings.has_setting(format!("autoload/{name}").into()) {
+            return;
+        }
 
-        let autoloads: Vec<(GString, GString, Gd<Node>)> = vec![(
-            "World".into(),
-            "res://autoloads/world.tscn".into(),
-            R3DWorld::new_alloc().upcast::<Node>(),
-        )];
+        if !ResourceLoader::singleton().exists(path.clone()) {
+            let file_path =
+                PathBuf::from(project_settings.globalize_path(path.clone()).to_string());
+            if let Some(directory) = file_path.parent() {
+                if let Err(error) = std::fs::create_dir_all(directory) {
+                    godot_error!("Couldn't create {}: {}", directory.display(), error);
+                    return;
+                }
+            }
 
-        for (name, path, instance) in autoloads.into_iter() {
-            let mut resource_saver = ResourceSaver::singleton();
             let mut packed_scene = PackedScene::new_gd();
-            packed_scene.pack(instance);
-            resource_saver
+            let world = R3DWorld::new_alloc();
+            packed_scene.pack(world.clone().upcast());
+            world.free();
+
+            let error = ResourceSaver::singleton()
                 .save_ex(packed_scene.upcast())
                 .path(path.clone())
                 .done();
-            self.base_mut().add_autoload_singleton(name, path);
+            if error != godot::engine::global::Error::OK {
+                godot_error!(
+                    "Couldn't save the R3DWorld autoload to {}: {:?}",
+                    path,
+                    error
+                );
+                return;
+            }
+        }
+
+        self.base_mut().add_autoload_singleton(name, path);
+    }
+
+    /// Removes the autoload if it still points at the configured scene. The
+    /// scene itself is left in place.
+    fn uninstall_autoload(&mut self) {
+        let name = R3DWorld::autoload_name();
+        let setting = format!("autoload/{name}");
+        let project_settings = ProjectSettings::singleton();
+        if !project_settings.has_setting(setting.as_str().into()) {
+            return;
+        }
+
+        let value = project_settings
+            .get_setting(setting.as_str().into())
+            .to::<GString>()
+            .to_string();
+        if value.trim_start_matches('*') == autoload_path().to_string() {
+            self.base_mut().remove_autoload_singleton(name);
         }
     }
 }
+
+fn autoload_enabled() -> bool {
+    let project_settings = ProjectSettings::singleton();
+    !project_settings.has_setting(world::AUTOLOAD_ENABLED_SETTING.into())
+        || project_settings
+            .get_setting(world::AUTOLOAD_ENABLED_SETTING.into())
+            .to::<bool>()
+}
+
+fn autoload_path() -> GString {
+    let project_settings = ProjectSettings::singleton();
+    if project_settings.has_setting(world::AUTOLOAD_PATH_SETTING.into()) {
+        project_settings
+            .get_setting(world::AUTOLOAD_PATH_SETTING.into())
+            .to::<GString>()
+    } else {
+        world::DEFAULT_AUTOLOAD_PATH.into()
+    }
+}
+
+/// Registers a project setting so it shows up in the project settings dialog,
+/// keeping any value the project already has.
+fn add_project_setting(name: &str, default: Variant, hint: PropertyHint, hint_string: &str) {
+    let mut project_settings = ProjectSettings::singleton();
+    if !project_settings.has_setting(name.into()) {
+        project_settings.set_setting(name.into(), default.clone());
+    }
+    project_settings.set_initial_value(name.into(), default.clone());
+
+    let mut info = Dictionary::new();
+    info.set("name", name);
+    info.set("type", default.get_type().ord());
+    info.set("hint", hint.ord());
+    info.set("hint_string", hint_string);
+    project_settings.add_property_info(info);
+}
@@ -0,0 +1,66 @@
+use godot::prelude::*;
+use rapier3d::prelude::*;
+
+#[derive(GodotConvert, Export, Var, PartialEq, Eq, Clone, Copy)]
+#[godot(via = u8)]
+pub enum CombineRule {
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl CombineRule {
+    pub fn coefficient_combine_rule(&self) -> CoefficientCombineRule {
+        match self {
+            CombineRule::Average => CoefficientCombineRule::Average,
+            CombineRule::Min => CoefficientCombineRule::Min,
+            CombineRule::Multiply => CoefficientCombineRule::Multiply,
+            CombineRule::Max => CoefficientCombineRule::Max,
+        }
+    }
+}
+
+/// Surface properties shared between colliders. When assigned to a collider
+/// it takes precedence over the collider's own restitution and density.
+#[derive(GodotClass)]
+#[class(tool, base = Resource)]
+pub struct R3DPhysicsMaterial {
+    #[export]
+    pub friction: f32,
+    #[export]
+    pub friction_combine_rule: CombineRule,
+    #[export]
+    pub restitution: f32,
+    #[export]
+    pub restitution_combine_rule: CombineRule,
+    #[export]
+    pub density: f32,
+
+    resource: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for R3DPhysicsMaterial {
+    fn init(resource: Base<Resource>) -> Self {
+        Self {
+            friction: 0.5,
+            friction_combine_rule: CombineRule::Average,
+            restitution: 0.0,
+            restitution_combine_rule: CombineRule::Average,
+            density: 1.0,
+            resource,
+        }
+    }
+}
+
+impl R3DPhysicsMaterial {
+    pub fn apply(&self, collider: &mut Collider) {
+        collider.set_friction(self.friction);
+        collider.set_friction_combine_rule(self.friction_combine_rule.coefficient_combine_rule());
+        collider.set_restitution(self.restitution);
+        collider
+            .set_restitution_combine_rule(self.restitution_combine_rule.coefficient_combine_rule());
+        collider.set_density(self.density);
+    }
+}
@@ -0,0 +1,375 @@
+use godot::{engine::Skeleton3D, prelude::*};
+use rapier3d::{na::UnitQuaternion, prelude::*};
+
+use crate::{
+    utils::{isometry_to_transform, transform_to_isometry},
+    world::R3DWorld,
+};
+
+#[derive(GodotConvert, Export, Var, PartialEq, Eq, Clone, Copy)]
+#[godot(via = u8)]
+pub enum RagdollMode {
+    Animated,
+    Simulated,
+}
+
+#[derive(GodotConvert, Export, Var, PartialEq, Eq, Clone, Copy)]
+#[godot(via = u8)]
+pub enum RagdollJointType {
+    Spherical,
+    Revolute,
+}
+
+/// Describes how a single skeleton bone is simulated. Bones without an entry
+/// are left to the animation.
+#[derive(GodotClass)]
+#[class(tool, base = Resource)]
+pub struct R3DRagdollBone {
+    #[export]
+    pub bone_name: GString,
+    #[export]
+    pub radius: f32,
+    #[export]
+    pub density: f32,
+    #[export]
+    pub joint_type: RagdollJointType,
+    /// Hinge axis in the bone's local space. Only used by revolute joints.
+    #[export]
+    pub joint_axis: Vector3,
+    /// Maximum rotation away from the rest pose in degrees on each free axis.
+    #[export]
+    pub angular_limit: f32,
+
+    resource: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for R3DRagdollBone {
+    fn init(resource: Base<Resource>) -> Self {
+        Self {
+            bone_name: GString::new(),
+            radius: 0.1,
+            density: 1.0,
+            joint_type: RagdollJointType::Spherical,
+            joint_axis: Vector3::RIGHT,
+            angular_limit: 45.0,
+            resource,
+        }
+    }
+}
+
+struct SimulatedBone {
+    bone_index: i32,
+    handle: RigidBodyHandle,
+}
+
+#[derive(GodotClass)]
+#[class(base = Node3D)]
+pub struct R3DRagdoll {
+    #[export]
+    skeleton: NodePath,
+    #[export]
+    bones: Array<Gd<R3DRagdollBone>>,
+    #[var(get, set = set_mode)]
+    #[export]
+    mode: RagdollMode,
+    /// How strongly simulated bodies override the animated pose while
+    /// simulated. 0 leaves the animation untouched, 1 fully follows physics.
+    #[export]
+    blend: f32,
+
+    world: Option<Gd<R3DWorld>>,
+    simulated_bones: Vec<SimulatedBone>,
+
+    node_3d: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for R3DRagdoll {
+    fn init(node_3d: Base<Node3D>) -> Self {
+        Self {
+            skeleton: NodePath::default(),
+            bones: Array::new(),
+            mode: RagdollMode::Animated,
+            blend: 1.0,
+
+            world: None,
+            simulated_bones: Vec::new(),
+
+            node_3d,
+        }
+    }
+
+    fn ready(&mut self) {
+        let mut node_3d = self.base_mut();
+        node_3d.set_process(true);
+        node_3d.add_to_group("networked".into());
+        drop(node_3d);
+
+        // The skeleton may be a child of this node, so wait until the
+        // subtree is ready before reading its pose.
+        self.register_ragdoll();
+    }
+
+    fn exit_tree(&mut self) {
+        self.unregister_ragdoll();
+        // Recreate the bone bodies in ready if this node is added back
+        self.base_mut().request_ready();
+    }
+
+    fn process(&mut self, _delta: f64) {
+        let Some(mut skeleton) = self.skeleton() else {
+            return;
+        };
+        let Some(world) = self.world.as_ref() else {
+            return;
+        };
+        let world = world.bind();
+
+        let skeleton_inverse = skeleton.get_global_transform().affine_inverse();
+        for bone in self.simulated_bones.iter() {
+            let Some(body) = world.body(bone.handle) else {
+                continue;
+            };
+
+            if body.is_dynamic() {
+                let pose = skeleton_inverse * isometry_to_transform(body.position());
+                skeleton.set_bone_global_pose_override(bone.bone_index, pose, self.blend, true);
+            } else {
+                skeleton.set_bone_global_pose_override(
+                    bone.bone_index,
+                    Transform3D::IDENTITY,
+                    0.0,
+                    false,
+                );
+            }
+        }
+    }
+}
+
+#[godot_api]
+impl R3DRagdoll {
+    #[func]
+    pub fn set_mode(&mut self, mode: RagdollMode) {
+        self.mode = mode;
+
+        let body_type = Self::body_type_for(mode);
+        if let Some(world) = self.world.as_mut() {
+            let mut world = world.bind_mut();
+            for bone in self.simulated_bones.iter() {
+                if let Some(body) = world.body_mut(bone.handle) {
+                    body.set_body_type(body_type, true);
+                    // Don't snap towards a pose left over from an earlier
+                    // kinematic phase
+                    let position = *body.position();
+                    body.set_next_kinematic_position(position);
+                }
+            }
+        }
+    }
+
+    /// Moves kinematic bone bodies towards the currently animated pose. The
+    /// body type is read from the world rather than `mode` so that rollbacks
+    /// restore which bones were following the animation.
+    #[func]
+    pub fn networked_preprocess(&mut self) {
+        let Some(skeleton) = self.skeleton() else {
+            return;
+        };
+        let Some(world) = self.world.as_mut() else {
+            return;
+        };
+        let mut world = world.bind_mut();
+
+        let skeleton_transform = skeleton.get_global_transform();
+        for bone in self.simulated_bones.iter() {
+            let Some(body) = world.body_mut(bone.handle) else {
+                continue;
+            };
+
+            if body.is_kinematic() {
+                let pose =
+                    skeleton_transform * skeleton.get_bone_global_pose_no_override(bone.bone_index);
+                body.set_next_kinematic_position(transform_to_isometry(&pose));
+            }
+        }
+    }
+
+    fn skeleton(&self) -> Option<Gd<Skeleton3D>> {
+        self.base()
+            .get_node_or_null(self.skeleton.clone())
+            .and_then(|node| node.try_cast::<Skeleton3D>().ok())
+    }
+
+    fn body_type_for(mode: RagdollMode) -> RigidBodyType {
+        match mode {
+            RagdollMode::Animated => RigidBodyType::KinematicPositionBased,
+            RagdollMode::Simulated => RigidBodyType::Dynamic,
+        }
+    }
+
+    fn register_ragdoll(&mut self) {
+        let Some(skeleton) = self.skeleton() else {
+            godot_error!("{} could not find its Skeleton3D", self.base().get_path());
+            return;
+        };
+
+        let Some(mut world_gd) = R3DWorld::find(&self.base()) else {
+            return;
+        };
+        let mut world = world_gd.bind_mut();
+
+        let node_path = self.base().get_path().to_string();
+        let skeleton_transform = skeleton.get_global_transform();
+        let body_type = Self::body_type_for(self.mode);
+
+        // Bodies are created in bone order so that every parent body exists
+        // before the joints of its children are created.
+        let mut bones = self
+            .bones
+            .iter_shared()
+            .filter_map(|bone| {
+                let index = skeleton.find_bone(bone.bind().bone_name.clone());
+                (index >= 0).then_some((index, bone))
+            })
+            .collect::<Vec<_>>();
+        bones.sort_by_key(|(index, _)| *index);
+
+        let mut created_bodies = Vec::new();
+        for (bone_index, bone) in bones.iter() {
+            let bone = bone.bind();
+            let key = format!("{node_path}/{}", bone.bone_name);
+            let handle = if let Some(handle) = world.find_body(&key) {
+                // Rolled back to a tick where the ragdoll already existed.
+                // Its colliders and joints are part of that state as well.
+                handle
+            } else {
+                let pose = skeleton_transform * skeleton.get_bone_global_pose(*bone_index);
+                let body = RigidBodyBuilder::new(body_type)
+                    .position(transform_to_isometry(&pose))
+                    .build();
+                let handle = world.insert_body(key, body);
+
+                let length = Self::bone_length(&skeleton, *bone_index, bone.radius);
+                let half_height = (length / 2.0 - bone.radius).max(0.0);
+                world.add_collider_to_body(
+                    ColliderBuilder::capsule_y(half_height, bone.radius)
+                        .translation(vector![0.0, length / 2.0, 0.0])
+                        .density(bone.density)
+                        .build(),
+                    handle,
+                );
+
+                created_bodies.push(handle);
+                handle
+            };
+
+            self.simulated_bones.push(SimulatedBone {
+                bone_index: *bone_index,
+                handle,
+            });
+        }
+
+        for (bone_index, bone) in bones.iter() {
+            let bone = bone.bind();
+            let Some(handle) = self.bone_handle(*bone_index) else {
+                continue;
+            };
+            if !created_bodies.contains(&handle) {
+                continue;
+            }
+
+            let Some(parent_handle) = self.mapped_parent_handle(&skeleton, *bone_index) else {
+                continue;
+            };
+
+            let (Some(parent_body), Some(body)) = (world.body(parent_handle), world.body(handle))
+            else {
+                continue;
+            };
+            let parent_position = *parent_body.position();
+            let position = *body.position();
+            let frame1 = parent_position.inverse() * position;
+            let limit = bone.angular_limit.to_radians();
+
+            let joint = match bone.joint_type {
+                RagdollJointType::Spherical => {
+                    GenericJointBuilder::new(JointAxesMask::LOCKED_SPHERICAL_AXES)
+                        .local_frame1(frame1)
+                        .local_frame2(Isometry::identity())
+                        .limits(JointAxis::AngX, [-limit, limit])
+                        .limits(JointAxis::AngY, [-limit, limit])
+                        .limits(JointAxis::AngZ, [-limit, limit])
+                        .contacts_enabled(false)
+                        .build()
+                }
+                RagdollJointType::Revolute => {
+                    // Revolute joints rotate around their local X axis, so
+                    // align it with the requested hinge axis.
+                    let axis = bone.joint_axis.normalized();
+                    let axis_rotation = UnitQuaternion::rotation_between(
+                        &Vector::x(),
+                        &vector![axis.x, axis.y, axis.z],
+                    )
+                    .unwrap_or_else(UnitQuaternion::identity);
+                    let axis_frame = Isometry::from_parts(Translation::identity(), axis_rotation);
+                    GenericJointBuilder::new(JointAxesMask::LOCKED_REVOLUTE_AXES)
+                        .local_frame1(frame1 * axis_frame)
+                        .local_frame2(axis_frame)
+                        .limits(JointAxis::AngX, [-limit, limit])
+                        .contacts_enabled(false)
+                        .build()
+                }
+            };
+            world.add_impulse_joint(parent_handle, handle, joint);
+        }
+
+        drop(world);
+        self.world = Some(world_gd);
+    }
+
+    fn unregister_ragdoll(&mut self) {
+        if let Some(mut world_gd) = self.world.take() {
+            let mut world = world_gd.bind_mut();
+            for bone in self.simulated_bones.drain(..) {
+                world.remove_body(bone.handle);
+            }
+        }
+
+        if let Some(mut skeleton) = self.skeleton() {
+            skeleton.clear_bones_global_pose_override();
+        }
+    }
+
+    fn bone_handle(&self, bone_index: i32) -> Option<RigidBodyHandle> {
+        self.simulated_bones
+            .iter()
+            .find(|bone| bone.bone_index == bone_index)
+            .map(|bone| bone.handle)
+    }
+
+    fn mapped_parent_handle(
+        &self,
+        skeleton: &Gd<Skeleton3D>,
+        bone_index: i32,
+    ) -> Option<RigidBodyHandle> {
+        let mut parent = skeleton.get_bone_parent(bone_index);
+        while parent >= 0 {
+            if let Some(handle) = self.bone_handle(parent) {
+                return Some(handle);
+            }
+            parent = skeleton.get_bone_parent(parent);
+        }
+        None
+    }
+
+    /// Distance from a bone to its first child, falling back to a sphere for
+    /// leaf bones such as hands and heads.
+    fn bone_length(skeleton: &Gd<Skeleton3D>, bone_index: i32, radius: f32) -> f32 {
+        let children = skeleton.get_bone_children(bone_index);
+        match children.as_slice().first() {
+            Some(child) => skeleton.get_bone_rest(*child).origin.length(),
+            None => radius * 2.0,
+        }
+    }
+}
@@ -9,7 +9,7 @@ use crate::rigid_body::R3DRigidBody;
 pub enum RaycastResult {
     None,
     Hit {
-        body: Gd<R3DRigidBody>,
+        body: Option<Gd<R3DRigidBody>>,
         distance: f32,
     },
 }
@@ -22,7 +22,8 @@ impl ToGodot for RaycastResult {
     fn to_godot(&self) -> Self::Via {
         let mut dictionary = Dictionary::new();
         if let RaycastResult::Hit { body, distance } = self {
-            dictionary.insert("body", body.clone());
+            // Null for static colliders so `result.body` still works in GDScript
+            dictionary.insert("body", body.to_variant());
             dictionary.insert("distance", *distance);
         }
         dictionary
@@ -1,24 +1,110 @@
+use godot::engine::{Engine, ProjectSettings};
 use godot::prelude::*;
 use rapier3d::prelude::*;
 
-use crate::{raycast_result::RaycastResult, world::R3DWorld};
+use crate::{
+    raycast_result::RaycastResult,
+    utils::{sync_manager_log, transform_to_isometry},
+    world::R3DWorld,
+};
 
-#[derive(GodotConvert, Export, Var, PartialEq, Eq)]
+#[derive(GodotConvert, Export, Var, PartialEq, Eq, Clone, Copy)]
 #[godot(via = u8)]
 pub enum BodyType {
     Dynamic,
     Fixed,
     Kinematic,
+    KinematicVelocity,
+}
+
+impl BodyType {
+    pub fn rigid_body_type(&self) -> RigidBodyType {
+        match self {
+            BodyType::Dynamic => RigidBodyType::Dynamic,
+            BodyType::Fixed => RigidBodyType::Fixed,
+            BodyType::Kinematic => RigidBodyType::KinematicPositionBased,
+            BodyType::KinematicVelocity => RigidBodyType::KinematicVelocityBased,
+        }
+    }
+
+    pub fn from_rigid_body_type(body_type: RigidBodyType) -> Self {
+        match body_type {
+            RigidBodyType::Dynamic => BodyType::Dynamic,
+            RigidBodyType::Fixed => BodyType::Fixed,
+            RigidBodyType::KinematicPositionBased => BodyType::Kinematic,
+            RigidBodyType::KinematicVelocityBased => BodyType::KinematicVelocity,
+        }
+    }
 }
 
 #[derive(GodotClass)]
-#[class(base = Node3D)]
+#[class(tool, base = Node3D)]
 pub struct R3DRigidBody {
+    /// The simulation properties are read from the live body once registered
+    /// so that rollbacks restore the values of that tick.
+    #[var(get = get_body_type, set = set_body_type)]
     #[export]
     pub body_type: BodyType,
+    /// When set, kinematic bodies use this node's transform as their target
+    /// every tick instead of writing the simulated transform back. Useful for
+    /// bodies moved by an AnimationPlayer.
+    #[export]
+    pub follow_node_transform: bool,
+    #[var(get = get_linear_damping, set = set_linear_damping)]
+    #[export]
+    pub linear_damping: f32,
+    #[var(get = get_angular_damping, set = set_angular_damping)]
+    #[export]
+    pub angular_damping: f32,
+    #[var(get = get_gravity_scale, set = set_gravity_scale)]
+    #[export]
+    pub gravity_scale: f32,
+    #[var(get = get_ccd_enabled, set = set_ccd_enabled)]
+    #[export]
+    pub ccd_enabled: bool,
+    /// Distance used to predict contacts ahead of time. 0 disables soft CCD.
+    #[var(get = get_soft_ccd_prediction, set = set_soft_ccd_prediction)]
+    #[export]
+    pub soft_ccd_prediction: f32,
+    #[var(get = get_can_sleep, set = set_can_sleep)]
+    #[export]
+    pub can_sleep: bool,
+    #[var(get = get_dominance_group, set = set_dominance_group)]
+    #[export(range = (-127.0, 127.0))]
+    pub dominance_group: i32,
+    #[var(get = get_additional_mass, set = set_additional_mass)]
+    #[export]
+    pub additional_mass: f32,
+    #[var(get = get_enabled_translations, set = set_enabled_translations)]
+    #[export(flags = (X = 1, Y = 2, Z = 4))]
+    pub enabled_translations: u32,
+    #[var(get = get_enabled_rotations, set = set_enabled_rotations)]
+    #[export(flags = (X = 1, Y = 2, Z = 4))]
+    pub enabled_rotations: u32,
+    /// Child that is moved smoothly between physics ticks while this node
+    /// stays on the simulated transform. Typically the body's mesh.
+    #[export]
+    pub visual_node: NodePath,
+    /// Blend the visuals towards corrected transforms after a rollback
+    /// instead of snapping. The simulated transform is unaffected.
+    #[export]
+    pub smooth_corrections: bool,
+    /// Seconds it takes a rollback correction to fade out.
+    #[export(range = (0.0, 2.0, or_greater))]
+    pub correction_time: f32,
 
     world: Option<Gd<R3DWorld>>,
     handle: Option<RigidBodyHandle>,
+    previous_transform: Transform3D,
+    current_transform: Transform3D,
+    /// Local transform the visual node was authored with.
+    visual_rest_transform: Transform3D,
+    /// Visual transform from before a rollback, turned into a correction
+    /// once the resimulated ticks have run.
+    pending_correction: Option<Transform3D>,
+    correction_translation: Vector3,
+    correction_rotation: Quaternion,
+    correction_remaining: f32,
 
     pub node_3d: Base<Node3D>,
 }
@@ -28,36 +114,109 @@ impl INode3D for R3DRigidBody {
     fn init(node_3d: Base<Node3D>) -> Self {
         Self {
             body_type: BodyType::Dynamic,
+            follow_node_transform: false,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            gravity_scale: 1.0,
+            ccd_enabled: false,
+            soft_ccd_prediction: 0.0,
+            can_sleep: true,
+            dominance_group: 0,
+            additional_mass: 0.0,
+            enabled_translations: 0b111,
+            enabled_rotations: 0b111,
+            visual_node: NodePath::default(),
+            smooth_corrections: false,
+            correction_time: 0.2,
 
             world: None,
             handle: None,
+            previous_transform: Transform3D::IDENTITY,
+            current_transform: Transform3D::IDENTITY,
+            visual_rest_transform: Transform3D::IDENTITY,
+            pending_correction: None,
+            correction_translation: Vector3::ZERO,
+            correction_rotation: Quaternion::new(0.0, 0.0, 0.0, 1.0),
+            correction_remaining: 0.0,
             node_3d,
         }
     }
 
     fn ready(&mut self) {
+        if Engine::singleton().is_editor_hint() {
+            return;
+        }
+
         let mut node_3d = self.base_mut();
         node_3d.set_process(true);
         node_3d.set_rotation_order(godot::engine::global::EulerOrder::ZXY);
         node_3d.add_to_group("networked".into());
+        drop(node_3d);
+
+        if let Some(visual_node) = self.visual_node() {
+            self.visual_rest_transform = visual_node.get_transform();
+        }
+    }
+
+    fn process(&mut self, delta: f64) {
+        if Engine::singleton().is_editor_hint() {
+            return;
+        }
+
+        self.update_correction(delta as f32);
+        let Some(mut visual_node) = self.visual_node() else {
+            return;
+        };
+        let transform = self.base().get_transform();
+        visual_node.set_transform(
+            transform.affine_inverse() * self.visual_transform() * self.visual_rest_transform,
+        );
     }
 
     fn enter_tree(&mut self) {
-        self.register_body();
+        if !Engine::singleton().is_editor_hint() {
+            self.register_body();
+            let transform = self.base().get_transform();
+            self.reset_interpolation(transform);
+        }
     }
 
     fn exit_tree(&mut self) {
         self.unregister_body();
     }
+
+    fn get_configuration_warnings(&self) -> PackedStringArray {
+        let mut warnings = PackedStringArray::new();
+        if !has_collider(&self.base().clone().upcast::<Node>()) {
+            warnings.push(
+                "This body has no colliders. Add an R3D collider as a descendant so it can collide."
+                    .into(),
+            );
+        }
+        let autoload_name = R3DWorld::autoload_name();
+        let autoload_setting = format!("autoload/{autoload_name}");
+        if !ProjectSettings::singleton().has_setting(autoload_setting.into()) {
+            warnings.push(
+                format!(
+                    "The {autoload_name} autoload is missing. Enable the gdrapier3d plugin to create it."
+                )
+                .into(),
+            );
+        }
+        warnings
+    }
 }
 
 #[godot_api]
 impl R3DRigidBody {
+    pub fn handle(&self) -> Option<RigidBodyHandle> {
+        self.handle
+    }
+
     pub fn add_collider(&mut self, collider: Collider) -> Option<ColliderHandle> {
-        if let Some(world) = self.world.as_mut() {
+        if let (Some(world), Some(handle)) = (self.world.as_mut(), self.handle) {
             let mut world = world.bind_mut();
-            let handle = world.add_collider_to_body(collider, self.handle.clone().unwrap());
-            Some(handle)
+            Some(world.add_collider_to_body(collider, handle))
         } else {
             None
         }
@@ -72,110 +231,487 @@ impl R3DRigidBody {
         }
     }
 
+    pub fn update_collider(
+        &mut self,
+        collider_handle: ColliderHandle,
+        update: impl FnOnce(&mut Collider),
+    ) {
+        if let Some(world) = self.world.as_mut() {
+            let mut world = world.bind_mut();
+            if let Some(collider) = world.collider_mut(collider_handle) {
+                update(collider);
+            }
+        }
+    }
+
     fn register_body(&mut self) {
-        let mut world = self
-            .base()
-            .get_node("/root/World".into())
-            .unwrap()
-            .cast::<R3DWorld>();
+        let Some(mut world) = R3DWorld::find(&self.base()) else {
+            return;
+        };
 
         self.handle = Some(world.bind_mut().add_body(self));
         self.world = Some(world);
     }
 
     fn unregister_body(&mut self) {
-        if let Some(handle) = self.handle.take() {
-            let mut world_gd = self.world.take().unwrap();
+        if let (Some(handle), Some(mut world_gd)) = (self.handle.take(), self.world.take()) {
             let mut world = world_gd.bind_mut();
             world.remove_body(handle);
         }
     }
 
+    fn visual_node(&self) -> Option<Gd<Node3D>> {
+        if self.visual_node.is_empty() {
+            return None;
+        }
+        self.base()
+            .get_node_or_null(self.visual_node.clone())
+            .and_then(|node| node.try_cast::<Node3D>().ok())
+    }
+
+    /// Moves this node to the transform simulated for a new tick, keeping the
+    /// previous one to interpolate from.
+    pub fn push_tick_transform(&mut self, transform: Transform3D) {
+        self.previous_transform = self.current_transform;
+        self.current_transform = transform;
+        self.base_mut().set_transform(transform);
+    }
+
+    /// Moves this node without interpolating from its previous transform.
+    pub fn reset_interpolation(&mut self, transform: Transform3D) {
+        self.previous_transform = transform;
+        self.current_transform = transform;
+        self.base_mut().set_transform(transform);
+    }
+
+    /// Called when the world rolls back to an earlier tick.
+    pub fn rollback(&mut self, transform: Transform3D) {
+        // Several rollbacks may happen before the next frame is drawn, and
+        // only the first one still knows what is on screen
+        if self.smooth_corrections && self.pending_correction.is_none() {
+            self.pending_correction = Some(self.visual_transform());
+        }
+        self.reset_interpolation(transform);
+    }
+
+    fn update_correction(&mut self, delta: f32) {
+        if let Some(before) = self.pending_correction.take() {
+            let after = self.interpolated_transform();
+            self.correction_translation = before.origin - after.origin;
+            self.correction_rotation = before.basis.to_quat() * after.basis.to_quat().inverse();
+            self.correction_remaining = self.correction_time;
+        } else {
+            self.correction_remaining = (self.correction_remaining - delta).max(0.0);
+        }
+    }
+
+    /// Transform between the previous and current tick, using how far the
+    /// engine is into the current physics frame.
+    fn interpolated_transform(&self) -> Transform3D {
+        let fraction = Engine::singleton().get_physics_interpolation_fraction();
+        self.previous_transform
+            .interpolate_with(&self.current_transform, fraction as f32)
+    }
+
+    /// The interpolated transform, offset by what is left of the last
+    /// rollback correction.
+    #[func]
+    pub fn visual_transform(&self) -> Transform3D {
+        let transform = self.interpolated_transform();
+        if self.correction_remaining <= 0.0 || self.correction_time <= 0.0 {
+            return transform;
+        }
+
+        let weight = self.correction_remaining / self.correction_time;
+        let rotation = Quaternion::new(0.0, 0.0, 0.0, 1.0).slerp(self.correction_rotation, weight);
+        Transform3D::new(
+            Basis::from_quat(rotation) * transform.basis,
+            transform.origin + self.correction_translation * weight,
+        )
+    }
+
+    /// Moves the body without sweeping through the space in between or
+    /// interpolating its visuals from the old transform.
+    #[func]
+    fn teleport(&mut self, transform: Transform3D) {
+        let position = transform_to_isometry(&transform);
+        self.write_body(|body| body.set_position(position, true));
+        self.reset_interpolation(transform);
+    }
+
+    /// Builds a body matching the exported properties of this node.
+    pub fn body_builder(&self) -> RigidBodyBuilder {
+        let [translate_x, translate_y, translate_z] = axis_flags(self.enabled_translations);
+        let [rotate_x, rotate_y, rotate_z] = axis_flags(self.enabled_rotations);
+        RigidBodyBuilder::new(self.body_type.rigid_body_type())
+            .linear_damping(self.linear_damping)
+            .angular_damping(self.angular_damping)
+            .gravity_scale(self.gravity_scale)
+            .ccd_enabled(self.ccd_enabled)
+            .soft_ccd_prediction(self.soft_ccd_prediction)
+            .can_sleep(self.can_sleep)
+            .dominance_group(self.dominance_group as i8)
+            .additional_mass(self.additional_mass)
+            .enabled_translations(translate_x, translate_y, translate_z)
+            .enabled_rotations(rotate_x, rotate_y, rotate_z)
+    }
+
+    fn update_body(&mut self, update: impl FnOnce(&mut RigidBody)) {
+        if let (Some(world), Some(handle)) = (self.world.as_mut(), self.handle) {
+            let mut world = world.bind_mut();
+            if let Some(body) = world.body_mut(handle) {
+                update(body);
+            }
+        }
+    }
+
+    /// Reads the registered body, if any. Unlike `read_body` this is quiet,
+    /// since property getters are also used before registering.
+    fn read_live_body<R>(&self, read: impl FnOnce(&RigidBody) -> R) -> Option<R> {
+        let (Some(world), Some(handle)) = (self.world.as_ref(), self.handle) else {
+            return None;
+        };
+        let world = world.bind();
+        world.body(handle).map(read)
+    }
+
+    #[func]
+    fn get_body_type(&self) -> BodyType {
+        self.read_live_body(|body| BodyType::from_rigid_body_type(body.body_type()))
+            .unwrap_or(self.body_type)
+    }
+
+    #[func]
+    fn set_body_type(&mut self, body_type: BodyType) {
+        self.body_type = body_type;
+        self.update_body(|body| {
+            let rigid_body_type = body_type.rigid_body_type();
+            if body.body_type() == rigid_body_type {
+                return;
+            }
+
+            // Velocities carry over so a kinematic ragdoll keeps its momentum
+            // when released. Rapier zeroes them when switching to fixed.
+            body.set_body_type(rigid_body_type, true);
+            if rigid_body_type == RigidBodyType::KinematicPositionBased {
+                // Don't drift towards a stale target from a previous kinematic phase
+                let position = *body.position();
+                body.set_next_kinematic_position(position);
+            }
+        });
+        if Engine::singleton().is_editor_hint() {
+            // Collider previews are colored by body type. Deferred since
+            // they read this body while it's still borrowed here.
+            self.base_mut()
+                .call_deferred("propagate_call".into(), &["update_debug_mesh".to_variant()]);
+        }
+    }
+
+    #[func]
+    fn get_linear_damping(&self) -> f32 {
+        self.read_live_body(|body| body.linear_damping())
+            .unwrap_or(self.linear_damping)
+    }
+
+    #[func]
+    fn set_linear_damping(&mut self, linear_damping: f32) {
+        self.linear_damping = linear_damping;
+        self.update_body(|body| body.set_linear_damping(linear_damping));
+    }
+
+    #[func]
+    fn get_angular_damping(&self) -> f32 {
+        self.read_live_body(|body| body.angular_damping())
+            .unwrap_or(self.angular_damping)
+    }
+
+    #[func]
+    fn set_angular_damping(&mut self, angular_damping: f32) {
+        self.angular_damping = angular_damping;
+        self.update_body(|body| body.set_angular_damping(angular_damping));
+    }
+
+    #[func]
+    fn get_gravity_scale(&self) -> f32 {
+        self.read_live_body(|body| body.gravity_scale())
+            .unwrap_or(self.gravity_scale)
+    }
+
+    #[func]
+    fn set_gravity_scale(&mut self, gravity_scale: f32) {
+        self.gravity_scale = gravity_scale;
+        self.update_body(|body| body.set_gravity_scale(gravity_scale, true));
+    }
+
+    #[func]
+    fn get_ccd_enabled(&self) -> bool {
+        self.read_live_body(|body| body.is_ccd_enabled())
+            .unwrap_or(self.ccd_enabled)
+    }
+
+    #[func]
+    fn set_ccd_enabled(&mut self, ccd_enabled: bool) {
+        self.ccd_enabled = ccd_enabled;
+        self.update_body(|body| body.enable_ccd(ccd_enabled));
+    }
+
+    #[func]
+    fn get_soft_ccd_prediction(&self) -> f32 {
+        self.read_live_body(|body| body.soft_ccd_prediction())
+            .unwrap_or(self.soft_ccd_prediction)
+    }
+
+    #[func]
+    fn set_soft_ccd_prediction(&mut self, soft_ccd_prediction: f32) {
+        self.soft_ccd_prediction = soft_ccd_prediction;
+        self.update_body(|body| body.set_soft_ccd_prediction(soft_ccd_prediction));
+    }
+
+    #[func]
+    fn get_can_sleep(&self) -> bool {
+        // Bodies that can't sleep have negative thresholds
+        self.read_live_body(|body| body.activation().linear_threshold >= 0.0)
+            .unwrap_or(self.can_sleep)
+    }
+
+    #[func]
+    fn set_can_sleep(&mut self, can_sleep: bool) {
+        self.can_sleep = can_sleep;
+        // Only the thresholds change, so sleeping bodies and timers are kept
+        let (linear_threshold, angular_threshold) = if can_sleep {
+            (
+                RigidBodyActivation::default_linear_threshold(),
+                RigidBodyActivation::default_angular_threshold(),
+            )
+        } else {
+            (-1.0, -1.0)
+        };
+        self.update_body(|body| {
+            let activation = body.activation_mut();
+            activation.linear_threshold = linear_threshold;
+            activation.angular_threshold = angular_threshold;
+        });
+    }
+
+    #[func]
+    fn get_dominance_group(&self) -> i32 {
+        self.read_live_body(|body| body.dominance_group() as i32)
+            .unwrap_or(self.dominance_group)
+    }
+
+    #[func]
+    fn set_dominance_group(&mut self, dominance_group: i32) {
+        self.dominance_group = dominance_group.clamp(-127, 127);
+        let dominance_group = self.dominance_group as i8;
+        self.update_body(|body| body.set_dominance_group(dominance_group));
+    }
+
+    #[func]
+    fn get_additional_mass(&self) -> f32 {
+        self.read_live_body(|body| {
+            match body.mass_properties().additional_local_mprops.as_deref() {
+                Some(RigidBodyAdditionalMassProps::Mass(mass)) => *mass,
+                Some(RigidBodyAdditionalMassProps::MassProps(props)) => props.mass(),
+                None => 0.0,
+            }
+        })
+        .unwrap_or(self.additional_mass)
+    }
+
+    #[func]
+    fn set_additional_mass(&mut self, additional_mass: f32) {
+        self.additional_mass = additional_mass;
+        self.update_body(|body| body.set_additional_mass(additional_mass, true));
+    }
+
+    #[func]
+    fn get_enabled_translations(&self) -> u32 {
+        self.read_live_body(|body| {
+            let locked = body.locked_axes();
+            axis_bits([
+                !locked.contains(LockedAxes::TRANSLATION_LOCKED_X),
+                !locked.contains(LockedAxes::TRANSLATION_LOCKED_Y),
+                !locked.contains(LockedAxes::TRANSLATION_LOCKED_Z),
+            ])
+        })
+        .unwrap_or(self.enabled_translations)
+    }
+
+    #[func]
+    fn set_enabled_translations(&mut self, enabled_translations: u32) {
+        self.enabled_translations = enabled_translations;
+        let [x, y, z] = axis_flags(enabled_translations);
+        self.update_body(|body| body.set_enabled_translations(x, y, z, true));
+    }
+
+    #[func]
+    fn get_enabled_rotations(&self) -> u32 {
+        self.read_live_body(|body| {
+            let locked = body.locked_axes();
+            axis_bits([
+                !locked.contains(LockedAxes::ROTATION_LOCKED_X),
+                !locked.contains(LockedAxes::ROTATION_LOCKED_Y),
+                !locked.contains(LockedAxes::ROTATION_LOCKED_Z),
+            ])
+        })
+        .unwrap_or(self.enabled_rotations)
+    }
+
+    #[func]
+    fn set_enabled_rotations(&mut self, enabled_rotations: u32) {
+        self.enabled_rotations = enabled_rotations;
+        let [x, y, z] = axis_flags(enabled_rotations);
+        self.update_body(|body| body.set_enabled_rotations(x, y, z, true));
+    }
+
+    /// Reads the registered body, reporting an error when this node isn't
+    /// registered with the world.
+    fn read_body<R>(&self, read: impl FnOnce(&RigidBody) -> R) -> Option<R> {
+        let result = match (self.world.as_ref(), self.handle) {
+            (Some(world), Some(handle)) => world.bind().body(handle).map(read),
+            _ => None,
+        };
+        if result.is_none() {
+            godot_error!(
+                "{} is not registered with the R3DWorld",
+                self.base().get_path()
+            );
+        }
+        result
+    }
+
+    fn write_body<R>(&mut self, write: impl FnOnce(&mut RigidBody) -> R) -> Option<R> {
+        let result = match (self.world.as_mut(), self.handle) {
+            (Some(world), Some(handle)) => world.bind_mut().body_mut(handle).map(write),
+            _ => None,
+        };
+        if result.is_none() {
+            godot_error!(
+                "{} is not registered with the R3DWorld",
+                self.base().get_path()
+            );
+        }
+        result
+    }
+
     #[func]
     fn networked_despawn(&mut self) {
-        let mut sync_manager = self.base().get_node("/root/SyncManager".into()).unwrap();
-        sync_manager.call("log".into(), &["Networked despawn called".to_variant()]);
+        sync_manager_log(&self.base(), "Networked despawn called".to_string());
         self.unregister_body();
     }
 
     #[func]
     fn raycast(&self, direction: Vector3, max_distance: f32) -> RaycastResult {
-        let handle = self.handle.unwrap();
+        let (Some(world), Some(handle)) = (self.world.as_ref(), self.handle) else {
+            godot_error!(
+                "{} is not registered with the R3DWorld",
+                self.base().get_path()
+            );
+            return RaycastResult::None;
+        };
         let mut direction = vector![direction.x, direction.y, direction.z];
         direction = direction.normalize();
-        self.world
-            .as_ref()
-            .unwrap()
-            .bind()
-            .raycast(handle, direction, max_distance)
+        world.bind().raycast(handle, direction, max_distance)
     }
 
     #[func]
     fn add_force(&mut self, force: Vector3) {
-        let handle = self.handle.unwrap();
-        let mut world = self.world.as_mut().unwrap().bind_mut();
-        let body = world.body_mut(handle).unwrap();
-        body.add_force(vector![force.x, force.y, force.z], true);
+        self.write_body(|body| body.add_force(vector![force.x, force.y, force.z], true));
     }
 
     #[func]
     fn add_torque(&mut self, torque: Vector3) {
-        let handle = self.handle.unwrap();
-        let mut world = self.world.as_mut().unwrap().bind_mut();
-        let body = world.body_mut(handle).unwrap();
-        body.add_torque(vector![torque.x, torque.y, torque.z], true);
+        self.write_body(|body| body.add_torque(vector![torque.x, torque.y, torque.z], true));
     }
 
     #[func]
     fn apply_impulse(&mut self, impulse: Vector3) {
-        let handle = self.handle.unwrap();
-        let mut world = self.world.as_mut().unwrap().bind_mut();
-        let body = world.body_mut(handle).unwrap();
-        body.apply_impulse(vector![impulse.x, impulse.y, impulse.z], true);
+        self.write_body(|body| body.apply_impulse(vector![impulse.x, impulse.y, impulse.z], true));
     }
 
     #[func]
     fn apply_torque_impulse(&mut self, torque_impulse: Vector3) {
-        let handle = self.handle.unwrap();
-        let mut world = self.world.as_mut().unwrap().bind_mut();
-        let body = world.body_mut(handle).unwrap();
-        body.apply_torque_impulse(
-            vector![torque_impulse.x, torque_impulse.y, torque_impulse.z],
-            true,
-        );
+        self.write_body(|body| {
+            body.apply_torque_impulse(
+                vector![torque_impulse.x, torque_impulse.y, torque_impulse.z],
+                true,
+            )
+        });
     }
 
     #[func]
     fn mass(&self) -> f32 {
-        let handle = self.handle.unwrap();
-        let world = self.world.as_ref().unwrap().bind();
-        let body = world.body(handle).unwrap();
-        body.mass()
+        self.read_body(|body| body.mass()).unwrap_or(0.0)
     }
 
     #[func]
     fn linear_velocity(&self) -> Vector3 {
-        let handle = self.handle.unwrap();
-        let world = self.world.as_ref().unwrap().bind();
-        let body = world.body(handle).unwrap();
-        let velocity = body.linvel();
-        Vector3::new(velocity.x, velocity.y, velocity.z)
+        self.read_body(|body| {
+            let velocity = body.linvel();
+            Vector3::new(velocity.x, velocity.y, velocity.z)
+        })
+        .unwrap_or(Vector3::ZERO)
     }
 
     #[func]
     fn set_linear_velocity(&mut self, velocity: Vector3) {
-        let handle = self.handle.unwrap();
-        let mut world = self.world.as_mut().unwrap().bind_mut();
-        let body = world.body_mut(handle).unwrap();
-        body.set_linvel(vector![velocity.x, velocity.y, velocity.z], true);
+        self.write_body(|body| body.set_linvel(vector![velocity.x, velocity.y, velocity.z], true));
     }
 
     #[func]
     fn angular_velocity(&self) -> Vector3 {
-        let handle = self.handle.unwrap();
-        let world = self.world.as_ref().unwrap().bind();
-        let body = world.body(handle).unwrap();
-        let velocity = body.angvel();
-        Vector3::new(velocity.x, velocity.y, velocity.z)
+        self.read_body(|body| {
+            let velocity = body.angvel();
+            Vector3::new(velocity.x, velocity.y, velocity.z)
+        })
+        .unwrap_or(Vector3::ZERO)
+    }
+
+    #[func]
+    fn set_angular_velocity(&mut self, velocity: Vector3) {
+        self.write_body(|body| body.set_angvel(vector![velocity.x, velocity.y, velocity.z], true));
+    }
+
+    #[func]
+    fn set_next_kinematic_transform(&mut self, transform: Transform3D) {
+        self.write_body(|body| body.set_next_kinematic_position(transform_to_isometry(&transform)));
+    }
+
+    #[func]
+    fn set_next_kinematic_translation(&mut self, translation: Vector3) {
+        self.write_body(|body| {
+            body.set_next_kinematic_translation(vector![
+                translation.x,
+                translation.y,
+                translation.z
+            ])
+        });
     }
+
+    #[func]
+    fn set_next_kinematic_rotation(&mut self, rotation: Quaternion) {
+        let transform = Transform3D::new(Basis::from_quat(rotation), Vector3::ZERO);
+        let rotation = transform_to_isometry(&transform).rotation;
+        self.write_body(|body| body.set_next_kinematic_rotation(rotation));
+    }
+}
+
+/// Whether any descendant collider belongs to this body rather than to a
+/// nested body.
+fn has_collider(node: &Gd<Node>) -> bool {
+    node.get_children().iter_shared().any(|child| {
+        if child.clone().try_cast::<R3DRigidBody>().is_ok() {
+            return false;
+        }
+        let class = child.get_class().to_string();
+        (class.starts_with("R3D") && class.ends_with("Collider")) || has_collider(&child)
+    })
+}
+
+fn axis_flags(flags: u32) -> [bool; 3] {
+    [flags & 1 != 0, flags & 2 != 0, flags & 4 != 0]
+}
+
+fn axis_bits([x, y, z]: [bool; 3]) -> u32 {
+    x as u32 | (y as u32) << 1 | (z as u32) << 2
 }
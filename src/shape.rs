@@ -0,0 +1,182 @@
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
+
+use godot::{
+    engine::{Mesh, Resource},
+    prelude::*,
+};
+use rapier3d::prelude::*;
+
+use crate::collider::{
+    ball_debug_mesh, ball_shape, capsule_debug_mesh, capsule_shape, cuboid_debug_mesh,
+    cuboid_shape, cylinder_debug_mesh, cylinder_shape, hull_debug_mesh, is_uniform_scale,
+    is_uniform_xz_scale, mesh_triangles, trimesh_shape,
+};
+
+/// Scales are rounded to this many steps per unit before a shape is built, so
+/// float noise from rotated parents doesn't defeat the cache.
+const SCALE_STEPS: f32 = 10_000.0;
+
+#[derive(GodotConvert, Export, Var, PartialEq, Eq, Clone, Copy)]
+#[godot(via = u8)]
+pub enum ShapeType {
+    Ball,
+    Box,
+    Capsule,
+    Cylinder,
+    Mesh,
+    Convex,
+}
+
+/// A collision shape that can be shared by many R3DShapeColliders. The
+/// built SharedShape is reference counted, so every collider using this
+/// resource at the same scale shares it.
+#[derive(GodotClass)]
+#[class(tool, base = Resource)]
+pub struct R3DShape {
+    #[var(get, set = set_shape_type)]
+    #[export]
+    shape_type: ShapeType,
+    /// Used by balls, capsules and cylinders.
+    #[var(get, set = set_radius)]
+    #[export]
+    radius: f32,
+    /// Used by capsules and cylinders.
+    #[var(get, set = set_half_height)]
+    #[export]
+    half_height: f32,
+    /// Used by boxes.
+    #[var(get, set = set_dimensions)]
+    #[export]
+    dimensions: Vector3,
+    /// Used by mesh and convex shapes.
+    #[var(get, set = set_mesh)]
+    #[export]
+    mesh: Option<Gd<Mesh>>,
+
+    cache: RefCell<HashMap<[i32; 3], SharedShape>>,
+
+    resource: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for R3DShape {
+    fn init(resource: Base<Resource>) -> Self {
+        Self {
+            shape_type: ShapeType::Ball,
+            radius: 0.5,
+            half_height: 1.0,
+            dimensions: Vector3::new(0.5, 0.5, 0.5),
+            mesh: None,
+
+            cache: RefCell::new(HashMap::new()),
+
+            resource,
+        }
+    }
+}
+
+#[godot_api]
+impl R3DShape {
+    #[func]
+    fn set_shape_type(&mut self, shape_type: ShapeType) {
+        self.shape_type = shape_type;
+        self.shape_changed();
+    }
+
+    #[func]
+    fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+        self.shape_changed();
+    }
+
+    #[func]
+    fn set_half_height(&mut self, half_height: f32) {
+        self.half_height = half_height;
+        self.shape_changed();
+    }
+
+    #[func]
+    fn set_dimensions(&mut self, dimensions: Vector3) {
+        self.dimensions = dimensions;
+        self.shape_changed();
+    }
+
+    #[func]
+    fn set_mesh(&mut self, mesh: Option<Gd<Mesh>>) {
+        self.mesh = mesh;
+        self.shape_changed();
+    }
+}
+
+impl R3DShape {
+    /// Drops the built shapes and lets the colliders using this resource
+    /// rebuild theirs.
+    fn shape_changed(&mut self) {
+        self.cache.borrow_mut().clear();
+        // Deferred since colliders read this resource while handling the
+        // signal, and it is still borrowed here
+        self.base_mut().call_deferred("emit_changed".into(), &[]);
+    }
+
+    pub fn shared_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        let key = scale_key(scale);
+        if let Some(shape) = self.cache.borrow().get(&key) {
+            return Some(shape.clone());
+        }
+
+        // Built from the rounded scale so the result doesn't depend on which
+        // collider asked first
+        let shape = self.build_shape(key_scale(key))?;
+        let mut cache = self.cache.borrow_mut();
+        // Forget shapes that no collider holds on to anymore
+        cache.retain(|_, shape| Arc::strong_count(&shape.0) > 1);
+        cache.insert(key, shape.clone());
+        Some(shape)
+    }
+
+    fn build_shape(&self, scale: Vector3) -> Option<SharedShape> {
+        match self.shape_type {
+            ShapeType::Ball => ball_shape(self.radius, scale),
+            ShapeType::Box => cuboid_shape(self.dimensions, scale),
+            ShapeType::Capsule => capsule_shape(self.radius, self.half_height, scale),
+            ShapeType::Cylinder => cylinder_shape(self.radius, self.half_height, scale),
+            ShapeType::Mesh => {
+                trimesh_shape(self.mesh.as_ref()?, &[], scale, TriMeshFlags::empty())
+            }
+            ShapeType::Convex => {
+                let (vertices, _) = mesh_triangles(self.mesh.as_ref()?, &[], scale)?;
+                SharedShape::convex_hull(&vertices)
+            }
+        }
+    }
+
+    pub fn debug_mesh(&self) -> Gd<Mesh> {
+        match self.shape_type {
+            ShapeType::Ball => ball_debug_mesh(self.radius),
+            ShapeType::Box => cuboid_debug_mesh(self.dimensions),
+            ShapeType::Capsule => capsule_debug_mesh(self.radius, self.half_height),
+            ShapeType::Cylinder => cylinder_debug_mesh(self.radius, self.half_height),
+            ShapeType::Mesh => self.mesh.clone().unwrap_or_else(Mesh::new_gd),
+            ShapeType::Convex => hull_debug_mesh(self.shared_shape(Vector3::ONE)),
+        }
+    }
+
+    /// Whether `shared_shape` represents this scale exactly rather than with
+    /// a convex approximation.
+    pub fn is_scale_exact(&self, scale: Vector3) -> bool {
+        let scale = key_scale(scale_key(scale));
+        match self.shape_type {
+            ShapeType::Ball | ShapeType::Capsule => is_uniform_scale(scale),
+            ShapeType::Cylinder => is_uniform_xz_scale(scale),
+            ShapeType::Box | ShapeType::Mesh | ShapeType::Convex => true,
+        }
+    }
+}
+
+fn scale_key(scale: Vector3) -> [i32; 3] {
+    [scale.x, scale.y, scale.z].map(|axis| (axis * SCALE_STEPS).round() as i32)
+}
+
+fn key_scale([x, y, z]: [i32; 3]) -> Vector3 {
+    Vector3::new(x as f32, y as f32, z as f32) / SCALE_STEPS
+}
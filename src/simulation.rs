@@ -0,0 +1,442 @@
+//! The physics simulation and its rollback frame store, independent of Godot.
+//! Bodies are identified by stable string keys that every peer agrees on,
+//! which R3DWorld derives from node paths.
+
+mod vehicle_state;
+
+#[cfg(test)]
+mod determinism;
+#[cfg(test)]
+mod tests;
+
+use std::collections::{BTreeMap, HashMap};
+
+use rapier3d::{
+    control::{EffectiveCharacterMovement, KinematicCharacterController},
+    prelude::*,
+};
+
+pub use self::vehicle_state::VehicleState;
+
+/// Everything that has to be rolled back. A copy is kept for every tick.
+#[derive(Clone)]
+pub struct WorldState {
+    rigid_body_set: RigidBodySet,
+    collider_set: ColliderSet,
+
+    // Simulation structures
+    gravity: Vector<Real>,
+    integration_parameters: IntegrationParameters,
+    query_pipeline: QueryPipeline,
+    island_manager: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    impulse_joint_set: ImpulseJointSet,
+    multibody_joint_set: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+
+    // State
+    /// Bodies mirrored by an outside object, such as an R3DRigidBody node.
+    tracked_bodies: HashMap<RigidBodyHandle, String>,
+    body_handles: HashMap<String, RigidBodyHandle>,
+    // Ordered so every peer updates vehicles in the same order
+    vehicles: BTreeMap<String, VehicleState>,
+}
+
+impl Default for WorldState {
+    fn default() -> Self {
+        Self {
+            rigid_body_set: RigidBodySet::new(),
+            collider_set: ColliderSet::new(),
+
+            gravity: vector![0.0, -9.81, 0.0],
+            integration_parameters: IntegrationParameters::default(),
+            query_pipeline: QueryPipeline::new(),
+            island_manager: IslandManager::new(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            impulse_joint_set: ImpulseJointSet::new(),
+            multibody_joint_set: MultibodyJointSet::new(),
+            ccd_solver: CCDSolver::new(),
+
+            tracked_bodies: HashMap::new(),
+            body_handles: HashMap::new(),
+            vehicles: BTreeMap::new(),
+        }
+    }
+}
+
+impl WorldState {
+    pub fn bodies(&self) -> &RigidBodySet {
+        &self.rigid_body_set
+    }
+
+    pub fn body_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody> {
+        self.rigid_body_set.get_mut(handle)
+    }
+
+    /// Finds a body by key if it exists in this state.
+    pub fn find_body(&self, key: &str) -> Option<RigidBodyHandle> {
+        self.body_handles
+            .get(key)
+            .copied()
+            .filter(|handle| self.rigid_body_set.contains(*handle))
+    }
+
+    /// Tracked bodies with their keys, in a deterministic order.
+    pub fn tracked_bodies(&self) -> impl Iterator<Item = (RigidBodyHandle, &str, &RigidBody)> {
+        self.rigid_body_set.iter().filter_map(|(handle, body)| {
+            let key = self.tracked_bodies.get(&handle)?;
+            Some((handle, key.as_str(), body))
+        })
+    }
+
+    pub fn tracked_bodies_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (RigidBodyHandle, &str, &mut RigidBody)> {
+        let tracked_bodies = &self.tracked_bodies;
+        self.rigid_body_set
+            .iter_mut()
+            .filter_map(move |(handle, body)| {
+                let key = tracked_bodies.get(&handle)?;
+                Some((handle, key.as_str(), body))
+            })
+    }
+
+    pub fn tracked_key(&self, handle: RigidBodyHandle) -> Option<&str> {
+        self.tracked_bodies.get(&handle).map(String::as_str)
+    }
+}
+
+/// Hit returned by [`Simulation::cast_ray`]. Static colliders have no body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub body: Option<RigidBodyHandle>,
+    pub distance: Real,
+}
+
+pub struct Simulation {
+    current_tick: u32,
+    frames: HashMap<u32, WorldState>,
+    physics_pipeline: PhysicsPipeline,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        let mut frames = HashMap::new();
+        frames.insert(0, WorldState::default());
+        Self {
+            current_tick: 0,
+            frames,
+            physics_pipeline: PhysicsPipeline::new(),
+        }
+    }
+
+    pub fn current_tick(&self) -> u32 {
+        self.current_tick
+    }
+
+    pub fn state(&self) -> &WorldState {
+        self.frames.get(&self.current_tick).unwrap()
+    }
+
+    fn state_mut(&mut self) -> &mut WorldState {
+        self.frames.get_mut(&self.current_tick).unwrap()
+    }
+
+    /// Rolls back to an earlier tick, discarding every later one. Returns
+    /// false if that tick was never simulated.
+    pub fn load_state(&mut self, tick: u32) -> bool {
+        if !self.frames.contains_key(&tick) {
+            return false;
+        }
+        self.current_tick = tick;
+        self.frames.retain(|k, _| *k <= tick);
+        true
+    }
+
+    /// Advances one tick. Equivalent to `step_with(|_| {})`.
+    pub fn step(&mut self) -> u32 {
+        self.step_with(|_| {})
+    }
+
+    /// Advances one tick. `prepare` can adjust the new tick's state, such as
+    /// kinematic targets, before it is simulated; the current tick is left
+    /// untouched so it can still be rolled back to.
+    pub fn step_with(&mut self, prepare: impl FnOnce(&mut WorldState)) -> u32 {
+        let mut frame = self.state().clone();
+        prepare(&mut frame);
+
+        let dt = frame.integration_parameters.dt;
+        for vehicle in frame.vehicles.values_mut() {
+            vehicle.update(
+                dt,
+                &mut frame.rigid_body_set,
+                &frame.collider_set,
+                &frame.query_pipeline,
+            );
+        }
+        self.physics_pipeline.step(
+            &frame.gravity,
+            &frame.integration_parameters,
+            &mut frame.island_manager,
+            &mut frame.broad_phase,
+            &mut frame.narrow_phase,
+            &mut frame.rigid_body_set,
+            &mut frame.collider_set,
+            &mut frame.impulse_joint_set,
+            &mut frame.multibody_joint_set,
+            &mut frame.ccd_solver,
+            None,
+            &(),
+            &(),
+        );
+        frame
+            .query_pipeline
+            .update(&frame.rigid_body_set, &frame.collider_set);
+        for (_, body) in frame.rigid_body_set.iter_mut() {
+            body.reset_forces(true);
+            body.reset_torques(true);
+        }
+
+        self.current_tick += 1;
+        self.frames.insert(self.current_tick, frame);
+        self.current_tick
+    }
+
+    pub fn body(&self, handle: RigidBodyHandle) -> Option<&RigidBody> {
+        self.state().rigid_body_set.get(handle)
+    }
+
+    pub fn body_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody> {
+        self.state_mut().rigid_body_set.get_mut(handle)
+    }
+
+    /// Finds a body by key if it exists on the current tick.
+    pub fn find_body(&self, key: &str) -> Option<RigidBodyHandle> {
+        self.state().find_body(key)
+    }
+
+    /// Inserts a body that is mirrored by an outside object.
+    pub fn insert_tracked_body(&mut self, key: String, body: RigidBody) -> RigidBodyHandle {
+        let handle = self.insert_body(key.clone(), body);
+        self.state_mut().tracked_bodies.insert(handle, key);
+        handle
+    }
+
+    /// Inserts a body that only exists in the simulation, such as a ragdoll
+    /// bone. The key must be stable across peers so that rollbacks can find
+    /// the body again.
+    pub fn insert_body(&mut self, key: String, body: RigidBody) -> RigidBodyHandle {
+        let frame = self.state_mut();
+        let handle = frame.rigid_body_set.insert(body);
+        frame.body_handles.insert(key, handle);
+        handle
+    }
+
+    pub fn remove_body(&mut self, handle: RigidBodyHandle) -> Option<RigidBody> {
+        let frame = self.state_mut();
+        frame.tracked_bodies.remove(&handle);
+        frame.body_handles.retain(|_, body| *body != handle);
+        frame.rigid_body_set.remove(
+            handle,
+            &mut frame.island_manager,
+            &mut frame.collider_set,
+            &mut frame.impulse_joint_set,
+            &mut frame.multibody_joint_set,
+            false,
+        )
+    }
+
+    pub fn add_impulse_joint(
+        &mut self,
+        body1: RigidBodyHandle,
+        body2: RigidBodyHandle,
+        joint: impl Into<GenericJoint>,
+    ) -> ImpulseJointHandle {
+        self.state_mut()
+            .impulse_joint_set
+            .insert(body1, body2, joint, true)
+    }
+
+    pub fn vehicle(&self, key: &str) -> Option<&VehicleState> {
+        self.state().vehicles.get(key)
+    }
+
+    pub fn vehicle_mut(&mut self, key: &str) -> Option<&mut VehicleState> {
+        self.state_mut().vehicles.get_mut(key)
+    }
+
+    pub fn add_vehicle(&mut self, key: String, vehicle: VehicleState) {
+        self.state_mut().vehicles.insert(key, vehicle);
+    }
+
+    pub fn remove_vehicle(&mut self, key: &str) {
+        self.state_mut().vehicles.remove(key);
+    }
+
+    pub fn add_collider_to_body(
+        &mut self,
+        collider: Collider,
+        body: RigidBodyHandle,
+    ) -> ColliderHandle {
+        let frame = self.state_mut();
+        frame
+            .collider_set
+            .insert_with_parent(collider, body, &mut frame.rigid_body_set)
+    }
+
+    /// Inserts a static collider that isn't attached to any body.
+    pub fn add_collider(&mut self, collider: Collider) -> ColliderHandle {
+        self.state_mut().collider_set.insert(collider)
+    }
+
+    pub fn collider_mut(&mut self, handle: ColliderHandle) -> Option<&mut Collider> {
+        self.state_mut().collider_set.get_mut(handle)
+    }
+
+    pub fn remove_collider(&mut self, handle: ColliderHandle) -> Option<Collider> {
+        let frame = self.state_mut();
+        frame.collider_set.remove(
+            handle,
+            &mut frame.island_manager,
+            &mut frame.rigid_body_set,
+            true,
+        )
+    }
+
+    pub fn debug_render(
+        &self,
+        pipeline: &mut DebugRenderPipeline,
+        backend: &mut impl DebugRenderBackend,
+    ) {
+        let frame = self.state();
+        pipeline.render(
+            backend,
+            &frame.rigid_body_set,
+            &frame.collider_set,
+            &frame.impulse_joint_set,
+            &frame.multibody_joint_set,
+            &frame.narrow_phase,
+        );
+    }
+
+    /// Dynamic bodies with a collider touching the sphere. A body is listed
+    /// once per touching collider.
+    pub fn bodies_within_sphere(
+        &self,
+        position: Point<Real>,
+        radius: Real,
+    ) -> Vec<RigidBodyHandle> {
+        let frame = self.state();
+        let mut bodies = Vec::new();
+        frame.query_pipeline.intersections_with_shape(
+            &frame.rigid_body_set,
+            &frame.collider_set,
+            &Isometry::translation(position.x, position.y, position.z),
+            &Ball::new(radius),
+            QueryFilter::only_dynamic(),
+            |collider| {
+                if let Some(body) = frame
+                    .collider_set
+                    .get(collider)
+                    .and_then(|collider| collider.parent())
+                {
+                    bodies.push(body);
+                }
+                true
+            },
+        );
+        bodies
+    }
+
+    /// Casts a ray from the body's center, ignoring the body itself.
+    pub fn cast_ray(
+        &self,
+        body: RigidBodyHandle,
+        direction: Vector<Real>,
+        max_distance: Real,
+    ) -> Option<RayHit> {
+        let frame = self.state();
+        let position = frame.rigid_body_set.get(body)?.translation();
+        let ray = Ray::new(Point::from(*position), direction.normalize());
+        let (collider, distance) = frame.query_pipeline.cast_ray(
+            &frame.rigid_body_set,
+            &frame.collider_set,
+            &ray,
+            max_distance,
+            true,
+            QueryFilter::default().exclude_rigid_body(body),
+        )?;
+
+        Some(RayHit {
+            body: frame
+                .collider_set
+                .get(collider)
+                .and_then(|collider| collider.parent()),
+            distance,
+        })
+    }
+
+    pub fn move_shape(
+        &self,
+        dt: Real,
+        character_shape: &dyn Shape,
+        character_pos: &Isometry<Real>,
+        desired_translation: Vector<Real>,
+
+        body_handle: RigidBodyHandle,
+    ) -> EffectiveCharacterMovement {
+        let frame = self.state();
+        let character_controller = KinematicCharacterController::default();
+        character_controller.move_shape(
+            dt,
+            &frame.rigid_body_set,
+            &frame.collider_set,
+            &frame.query_pipeline,
+            character_shape,
+            character_pos,
+            desired_translation,
+            QueryFilter::default().exclude_rigid_body(body_handle),
+            |_| {},
+        )
+    }
+
+    /// Hash of every body's position and velocities on the current tick.
+    /// Uses FNV-1a over the raw float bits so it is stable across platforms
+    /// and Rust versions.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+        let mut write = |value: Real| {
+            for byte in value.to_bits().to_le_bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        };
+
+        for (_, body) in self.state().rigid_body_set.iter() {
+            let position = body.position();
+            position
+                .translation
+                .vector
+                .iter()
+                .copied()
+                .for_each(&mut write);
+            position
+                .rotation
+                .coords
+                .iter()
+                .copied()
+                .for_each(&mut write);
+            body.linvel().iter().copied().for_each(&mut write);
+            body.angvel().iter().copied().for_each(&mut write);
+        }
+        hash
+    }
+}
@@ -0,0 +1,335 @@
+//! Determinism regression harness. Scripted scenarios are run headless and
+//! the state hash of every tick is compared against the golden files in
+//! `src/simulation/golden`.
+//!
+//! A missing golden file fails the check. Run with `UPDATE_GOLDEN=1` to
+//! record every file, and again after an intentional change to the
+//! simulation, such as a rapier upgrade.
+
+use std::{env, fmt::Write, fs, path::PathBuf};
+
+use rapier3d::prelude::*;
+
+use super::{Simulation, WorldState};
+
+const DT: Real = 1.0 / 60.0;
+
+/// Input scripted for a single tick. Bodies are addressed by key so the same
+/// script can be replayed after a rollback.
+enum Command {
+    Impulse(String, Vector<Real>),
+    NextKinematicTranslation(String, Vector<Real>),
+}
+
+struct Scenario {
+    name: &'static str,
+    ticks: u32,
+    setup: fn() -> Simulation,
+    /// Commands for simulating from `tick` to `tick + 1`.
+    input: fn(&Simulation, u32) -> Vec<Command>,
+}
+
+const SCENARIOS: [Scenario; 4] = [
+    Scenario {
+        name: "stack",
+        ticks: 240,
+        setup: stack,
+        input: no_input,
+    },
+    Scenario {
+        name: "ragdoll",
+        ticks: 240,
+        setup: ragdoll,
+        input: no_input,
+    },
+    Scenario {
+        name: "character",
+        ticks: 180,
+        setup: character,
+        input: character_input,
+    },
+    Scenario {
+        name: "rollback",
+        ticks: 180,
+        setup: stack,
+        input: projectile_input,
+    },
+];
+
+fn ground(simulation: &mut Simulation) {
+    simulation.add_collider(
+        ColliderBuilder::cuboid(20.0, 0.5, 20.0)
+            .translation(vector![0.0, -0.5, 0.0])
+            .build(),
+    );
+}
+
+/// A slightly staggered tower of boxes that topples over.
+fn stack() -> Simulation {
+    let mut simulation = Simulation::new();
+    ground(&mut simulation);
+    for i in 0..10 {
+        let body = simulation.insert_tracked_body(
+            format!("box/{i}"),
+            RigidBodyBuilder::dynamic()
+                .translation(vector![i as Real * 0.08, 0.5 + i as Real * 1.01, 0.0])
+                .build(),
+        );
+        simulation.add_collider_to_body(ColliderBuilder::cuboid(0.5, 0.5, 0.5).build(), body);
+    }
+    simulation
+}
+
+/// A chain of capsules joined like ragdoll bones, dropped onto the ground.
+fn ragdoll() -> Simulation {
+    let mut simulation = Simulation::new();
+    ground(&mut simulation);
+
+    let limit = 45.0_f32.to_radians();
+    let mut parent = None;
+    for i in 0..6 {
+        let body = simulation.insert_body(
+            format!("ragdoll/{i}"),
+            RigidBodyBuilder::dynamic()
+                .translation(vector![i as Real * 0.6, 4.0, 0.0])
+                .rotation(vector![0.0, 0.0, std::f32::consts::FRAC_PI_2])
+                .build(),
+        );
+        simulation.add_collider_to_body(ColliderBuilder::capsule_y(0.2, 0.1).build(), body);
+
+        if let Some(parent) = parent {
+            let joint = GenericJointBuilder::new(JointAxesMask::LOCKED_SPHERICAL_AXES)
+                .local_anchor1(point![0.0, -0.3, 0.0])
+                .local_anchor2(point![0.0, 0.3, 0.0])
+                .limits(JointAxis::AngX, [-limit, limit])
+                .limits(JointAxis::AngY, [-limit, limit])
+                .limits(JointAxis::AngZ, [-limit, limit])
+                .contacts_enabled(false)
+                .build();
+            simulation.add_impulse_joint(parent, body, joint);
+        }
+        parent = Some(body);
+    }
+    simulation
+}
+
+/// A kinematic capsule walking into a step and up a ramp.
+fn character() -> Simulation {
+    let mut simulation = Simulation::new();
+    ground(&mut simulation);
+    simulation.add_collider(
+        ColliderBuilder::cuboid(0.5, 0.1, 2.0)
+            .translation(vector![2.0, 0.1, 0.0])
+            .build(),
+    );
+    simulation.add_collider(
+        ColliderBuilder::cuboid(2.0, 0.1, 2.0)
+            .translation(vector![5.0, 0.5, 0.0])
+            .rotation(vector![0.0, 0.0, 0.3])
+            .build(),
+    );
+
+    let body = simulation.insert_tracked_body(
+        "character".to_string(),
+        RigidBodyBuilder::kinematic_position_based()
+            .translation(vector![0.0, 1.0, 0.0])
+            .build(),
+    );
+    simulation.add_collider_to_body(ColliderBuilder::capsule_y(0.5, 0.3).build(), body);
+    simulation
+}
+
+fn no_input(_simulation: &Simulation, _tick: u32) -> Vec<Command> {
+    Vec::new()
+}
+
+fn character_input(simulation: &Simulation, tick: u32) -> Vec<Command> {
+    let Some(handle) = simulation.find_body("character") else {
+        return Vec::new();
+    };
+    let position = *simulation.body(handle).unwrap().position();
+    // Walk along +X, turning towards +Z half way through
+    let walk = if tick < 90 {
+        vector![3.0, 0.0, 0.0]
+    } else {
+        vector![2.0, 0.0, 2.0]
+    };
+    let desired = (walk + vector![0.0, -9.81, 0.0]) * DT;
+
+    let movement = simulation.move_shape(DT, &Capsule::new_y(0.5, 0.3), &position, desired, handle);
+    vec![Command::NextKinematicTranslation(
+        "character".to_string(),
+        position.translation.vector + movement.translation,
+    )]
+}
+
+/// The middle of the stack is knocked sideways halfway through.
+fn projectile_input(_simulation: &Simulation, tick: u32) -> Vec<Command> {
+    match tick {
+        90 => vec![Command::Impulse(
+            "box/5".to_string(),
+            vector![6.0, 0.0, 2.0],
+        )],
+        _ => Vec::new(),
+    }
+}
+
+fn apply(state: &mut WorldState, commands: Vec<Command>) {
+    for command in commands {
+        match command {
+            Command::Impulse(key, impulse) => {
+                if let Some(body) = state.find_body(&key).and_then(|h| state.body_mut(h)) {
+                    body.apply_impulse(impulse, true);
+                }
+            }
+            Command::NextKinematicTranslation(key, translation) => {
+                if let Some(body) = state.find_body(&key).and_then(|h| state.body_mut(h)) {
+                    body.set_next_kinematic_translation(translation);
+                }
+            }
+        }
+    }
+}
+
+/// Simulates up to `until`, returning the hash of every new tick.
+fn run_until(
+    simulation: &mut Simulation,
+    input: fn(&Simulation, u32) -> Vec<Command>,
+    until: u32,
+) -> Vec<u64> {
+    let mut hashes = Vec::new();
+    while simulation.current_tick() < until {
+        let commands = input(simulation, simulation.current_tick());
+        simulation.step_with(|state| apply(state, commands));
+        hashes.push(simulation.state_hash());
+    }
+    hashes
+}
+
+fn run(scenario: &Scenario) -> Vec<u64> {
+    let mut simulation = (scenario.setup)();
+    run_until(&mut simulation, scenario.input, scenario.ticks)
+}
+
+/// Runs like a peer that mispredicted: no input at all until `detected`,
+/// then rolls back to `rollback_to` and resimulates with the real input.
+/// Returns the hashes of the corrected timeline.
+fn run_with_rollback(scenario: &Scenario, rollback_to: u32, detected: u32) -> Vec<u64> {
+    let mut simulation = (scenario.setup)();
+    let mut hashes = run_until(&mut simulation, scenario.input, rollback_to);
+    run_until(&mut simulation, no_input, detected);
+
+    assert!(simulation.load_state(rollback_to));
+    hashes.extend(run_until(&mut simulation, scenario.input, scenario.ticks));
+    hashes
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/simulation/golden")
+        .join(format!("{name}.txt"))
+}
+
+fn format_hashes(hashes: &[u64]) -> String {
+    let mut text = String::new();
+    for (tick, hash) in hashes.iter().enumerate() {
+        writeln!(text, "{} {hash:016x}", tick + 1).unwrap();
+    }
+    text
+}
+
+fn check_golden(name: &str, hashes: &[u64]) {
+    let path = golden_path(name);
+    let actual = format_hashes(hashes);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|error| {
+        panic!(
+            "Couldn't read {}: {error}. Run with UPDATE_GOLDEN=1 to record it.",
+            path.display()
+        )
+    });
+    if let Some((expected, actual)) = expected
+        .lines()
+        .zip(actual.lines())
+        .find(|(expected, actual)| expected != actual)
+    {
+        panic!(
+            "{name} diverged from {}: expected `{expected}`, got `{actual}`",
+            path.display()
+        );
+    }
+    assert_eq!(
+        expected.lines().count(),
+        actual.lines().count(),
+        "{name} ran for a different number of ticks than {}",
+        path.display()
+    );
+}
+
+fn scenario(name: &str) -> &'static Scenario {
+    SCENARIOS
+        .iter()
+        .find(|scenario| scenario.name == name)
+        .unwrap()
+}
+
+#[test]
+fn stack_matches_golden() {
+    check_golden("stack", &run(scenario("stack")));
+}
+
+#[test]
+fn ragdoll_matches_golden() {
+    check_golden("ragdoll", &run(scenario("ragdoll")));
+}
+
+#[test]
+fn character_matches_golden() {
+    check_golden("character", &run(scenario("character")));
+}
+
+#[test]
+fn rollback_matches_golden() {
+    check_golden(
+        "rollback",
+        &run_with_rollback(scenario("rollback"), 80, 110),
+    );
+}
+
+#[test]
+fn runs_are_repeatable() {
+    for scenario in SCENARIOS.iter() {
+        assert_eq!(run(scenario), run(scenario), "{}", scenario.name);
+    }
+}
+
+#[test]
+fn rollback_and_resimulate_matches_straight_run() {
+    for scenario in SCENARIOS.iter() {
+        let straight = run(scenario);
+        for rollback_to in [0, 1, scenario.ticks / 3, scenario.ticks / 2] {
+            let mut simulation = (scenario.setup)();
+            run_until(&mut simulation, scenario.input, scenario.ticks - 10);
+            assert!(simulation.load_state(rollback_to));
+
+            let resimulated = run_until(&mut simulation, scenario.input, scenario.ticks);
+            assert_eq!(
+                resimulated,
+                straight[rollback_to as usize..],
+                "{} resimulated from tick {rollback_to}",
+                scenario.name
+            );
+        }
+    }
+}
+
+#[test]
+fn corrected_misprediction_matches_straight_run() {
+    let scenario = scenario("rollback");
+    assert_eq!(run_with_rollback(scenario, 80, 110), run(scenario));
+}
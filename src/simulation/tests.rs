@@ -0,0 +1,212 @@
+use rapier3d::prelude::*;
+
+use super::Simulation;
+
+/// A 20x1x20 ground slab with its top at y = 0 and a ball dropped onto it.
+fn ball_on_ground(height: Real) -> (Simulation, RigidBodyHandle) {
+    let mut simulation = Simulation::new();
+    simulation.add_collider(
+        ColliderBuilder::cuboid(10.0, 0.5, 10.0)
+            .translation(vector![0.0, -0.5, 0.0])
+            .build(),
+    );
+    let ball = simulation.insert_tracked_body(
+        "ball".to_string(),
+        RigidBodyBuilder::dynamic()
+            .translation(vector![0.0, height, 0.0])
+            .build(),
+    );
+    simulation.add_collider_to_body(ColliderBuilder::ball(0.5).build(), ball);
+    (simulation, ball)
+}
+
+fn height(simulation: &Simulation, body: RigidBodyHandle) -> Real {
+    simulation.body(body).unwrap().translation().y
+}
+
+#[test]
+fn step_advances_tick_and_applies_gravity() {
+    let (mut simulation, ball) = ball_on_ground(5.0);
+    assert_eq!(simulation.current_tick(), 0);
+
+    assert_eq!(simulation.step(), 1);
+    assert_eq!(simulation.current_tick(), 1);
+    assert!(height(&simulation, ball) < 5.0);
+    assert!(simulation.body(ball).unwrap().linvel().y < 0.0);
+}
+
+#[test]
+fn ball_comes_to_rest_on_ground() {
+    let (mut simulation, ball) = ball_on_ground(2.0);
+    for _ in 0..300 {
+        simulation.step();
+    }
+
+    assert!((height(&simulation, ball) - 0.5).abs() < 0.05);
+}
+
+#[test]
+fn step_leaves_previous_tick_untouched() {
+    let (mut simulation, ball) = ball_on_ground(5.0);
+    simulation.step_with(|state| {
+        for (_, key, body) in state.tracked_bodies_mut() {
+            assert_eq!(key, "ball");
+            body.set_linvel(vector![1.0, 0.0, 0.0], true);
+        }
+    });
+    assert!(simulation.body(ball).unwrap().translation().x > 0.0);
+
+    assert!(simulation.load_state(0));
+    assert_eq!(simulation.body(ball).unwrap().translation().x, 0.0);
+    assert_eq!(
+        *simulation.body(ball).unwrap().linvel(),
+        vector![0.0, 0.0, 0.0]
+    );
+}
+
+#[test]
+fn load_state_restores_and_discards_later_ticks() {
+    let (mut simulation, ball) = ball_on_ground(5.0);
+    for _ in 0..5 {
+        simulation.step();
+    }
+    let height_at_5 = height(&simulation, ball);
+    for _ in 0..5 {
+        simulation.step();
+    }
+    assert_ne!(height(&simulation, ball), height_at_5);
+
+    assert!(simulation.load_state(5));
+    assert_eq!(simulation.current_tick(), 5);
+    assert_eq!(height(&simulation, ball), height_at_5);
+    // Tick 10 was discarded along with everything after tick 5
+    assert!(!simulation.load_state(10));
+    assert_eq!(simulation.current_tick(), 5);
+}
+
+#[test]
+fn load_state_rejects_unsimulated_ticks() {
+    let (mut simulation, _) = ball_on_ground(5.0);
+    simulation.step();
+
+    assert!(!simulation.load_state(7));
+    assert_eq!(simulation.current_tick(), 1);
+}
+
+#[test]
+fn rollback_removes_bodies_added_later() {
+    let (mut simulation, _) = ball_on_ground(5.0);
+    simulation.step();
+    let late = simulation.insert_body("late".to_string(), RigidBodyBuilder::dynamic().build());
+    assert_eq!(simulation.find_body("late"), Some(late));
+    simulation.step();
+
+    assert!(simulation.load_state(0));
+    assert_eq!(simulation.find_body("late"), None);
+    assert!(simulation.find_body("ball").is_some());
+}
+
+#[test]
+fn removed_bodies_are_forgotten() {
+    let (mut simulation, ball) = ball_on_ground(5.0);
+    assert!(simulation.remove_body(ball).is_some());
+
+    assert_eq!(simulation.find_body("ball"), None);
+    assert_eq!(simulation.state().tracked_key(ball), None);
+    assert_eq!(simulation.state().tracked_bodies().count(), 0);
+}
+
+#[test]
+fn only_tracked_bodies_are_listed_as_tracked() {
+    let (mut simulation, ball) = ball_on_ground(5.0);
+    let bone = simulation.insert_body(
+        "ragdoll/bone".to_string(),
+        RigidBodyBuilder::dynamic().build(),
+    );
+
+    let tracked = simulation
+        .state()
+        .tracked_bodies()
+        .map(|(handle, key, _)| (handle, key.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(tracked, vec![(ball, "ball".to_string())]);
+    assert_eq!(simulation.find_body("ragdoll/bone"), Some(bone));
+}
+
+#[test]
+fn cast_ray_hits_static_ground() {
+    let (mut simulation, ball) = ball_on_ground(3.0);
+    // The query pipeline is updated as part of a step
+    simulation.step();
+
+    let hit = simulation
+        .cast_ray(ball, vector![0.0, -1.0, 0.0], 10.0)
+        .unwrap();
+    assert_eq!(hit.body, None);
+    assert!((hit.distance - height(&simulation, ball)).abs() < 1e-4);
+
+    assert_eq!(
+        simulation.cast_ray(ball, vector![0.0, 1.0, 0.0], 10.0),
+        None
+    );
+}
+
+#[test]
+fn cast_ray_reports_hit_body() {
+    let (mut simulation, ball) = ball_on_ground(3.0);
+    let target = simulation.insert_tracked_body(
+        "target".to_string(),
+        RigidBodyBuilder::fixed()
+            .translation(vector![5.0, 3.0, 0.0])
+            .build(),
+    );
+    simulation.add_collider_to_body(ColliderBuilder::cuboid(0.5, 0.5, 0.5).build(), target);
+    simulation.step();
+
+    let hit = simulation
+        .cast_ray(ball, vector![1.0, 0.0, 0.0], 10.0)
+        .unwrap();
+    assert_eq!(hit.body, Some(target));
+    assert!((hit.distance - 4.5).abs() < 0.05);
+}
+
+#[test]
+fn bodies_within_sphere_finds_dynamic_bodies_only() {
+    let (mut simulation, ball) = ball_on_ground(3.0);
+    simulation.step();
+    let position = *simulation.body(ball).unwrap().translation();
+
+    let found = simulation.bodies_within_sphere(Point::from(position), 1.0);
+    assert_eq!(found, vec![ball]);
+    // The ground is static and never reported
+    assert!(simulation
+        .bodies_within_sphere(point![0.0, -0.5, 0.0], 1.0)
+        .is_empty());
+}
+
+#[test]
+fn identical_worlds_stay_identical() {
+    let (mut first, _) = ball_on_ground(5.0);
+    let (mut second, _) = ball_on_ground(5.0);
+    for _ in 0..120 {
+        first.step();
+        second.step();
+        assert_eq!(first.state_hash(), second.state_hash());
+    }
+}
+
+#[test]
+fn resimulating_after_rollback_is_deterministic() {
+    let (mut simulation, _) = ball_on_ground(5.0);
+    let mut hashes = Vec::new();
+    for _ in 0..60 {
+        simulation.step();
+        hashes.push(simulation.state_hash());
+    }
+
+    assert!(simulation.load_state(20));
+    for expected in hashes.iter().skip(20) {
+        simulation.step();
+        assert_eq!(simulation.state_hash(), *expected);
+    }
+}
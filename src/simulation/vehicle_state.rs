@@ -0,0 +1,102 @@
+use rapier3d::{
+    control::{DynamicRayCastVehicleController, Wheel, WheelTuning},
+    prelude::*,
+};
+
+/// Rollback friendly snapshot of a `DynamicRayCastVehicleController`. The
+/// controller itself can't be cloned, so it is rebuilt from this every tick.
+#[derive(Clone)]
+pub struct VehicleState {
+    chassis: RigidBodyHandle,
+    wheels: Vec<Wheel>,
+    current_vehicle_speed: Real,
+}
+
+impl VehicleState {
+    pub fn new(chassis: RigidBodyHandle) -> Self {
+        Self {
+            chassis,
+            wheels: Vec::new(),
+            current_vehicle_speed: 0.0,
+        }
+    }
+
+    pub fn add_wheel(
+        &mut self,
+        chassis_connection: Point<Real>,
+        direction: Vector<Real>,
+        axle: Vector<Real>,
+        suspension_rest_length: Real,
+        radius: Real,
+        tuning: &WheelTuning,
+    ) {
+        let mut controller = DynamicRayCastVehicleController::new(self.chassis);
+        let wheel = *controller.add_wheel(
+            chassis_connection,
+            direction,
+            axle,
+            suspension_rest_length,
+            radius,
+            tuning,
+        );
+        self.wheels.push(wheel);
+    }
+
+    pub fn chassis(&self) -> RigidBodyHandle {
+        self.chassis
+    }
+
+    pub fn current_vehicle_speed(&self) -> Real {
+        self.current_vehicle_speed
+    }
+
+    pub fn wheels(&self) -> &[Wheel] {
+        &self.wheels
+    }
+
+    pub fn wheels_mut(&mut self) -> &mut [Wheel] {
+        &mut self.wheels
+    }
+
+    pub fn update(
+        &mut self,
+        dt: Real,
+        bodies: &mut RigidBodySet,
+        colliders: &ColliderSet,
+        queries: &QueryPipeline,
+    ) {
+        if !bodies.contains(self.chassis) {
+            return;
+        }
+
+        let mut controller = self.controller();
+        controller.update_vehicle(
+            dt,
+            bodies,
+            colliders,
+            queries,
+            QueryFilter::exclude_dynamic().exclude_rigid_body(self.chassis),
+        );
+
+        self.wheels.copy_from_slice(controller.wheels());
+        self.current_vehicle_speed = controller.current_vehicle_speed;
+    }
+
+    fn controller(&self) -> DynamicRayCastVehicleController {
+        let mut controller = DynamicRayCastVehicleController::new(self.chassis);
+        // Godot's vehicles drive along +Z
+        controller.index_forward_axis = 2;
+        controller.current_vehicle_speed = self.current_vehicle_speed;
+        for wheel in self.wheels.iter() {
+            *controller.add_wheel(
+                wheel.chassis_connection_point_cs,
+                wheel.direction_cs,
+                wheel.axle_cs,
+                wheel.suspension_rest_length,
+                wheel.radius,
+                &WheelTuning::default(),
+            ) = *wheel;
+        }
+        controller
+    }
+}
@@ -1,5 +1,18 @@
 use godot::prelude::*;
-use rapier3d::prelude::{Isometry, Real};
+use rapier3d::{
+    na::{Quaternion as NaQuaternion, UnitQuaternion},
+    prelude::{Isometry, Real, Translation},
+};
+
+/// Forwards a message to gdrollback's SyncManager log, falling back to the
+/// Godot output when the SyncManager isn't loaded.
+pub fn sync_manager_log(node: &Node, message: String) {
+    if let Some(mut sync_manager) = node.get_node_or_null("/root/SyncManager".into()) {
+        sync_manager.call("log".into(), &[message.to_variant()]);
+    } else {
+        godot_print!("{message}");
+    }
+}
 
 pub fn perpendicular(v: Vector3) -> Vector3 {
     // Taking the crossproduct with the basis vectors will
@@ -45,3 +58,14 @@ pub fn isometry_to_transform(isometry: &Isometry<Real>) -> Transform3D {
     ]);
     Transform3D::from_projection(projection)
 }
+
+pub fn transform_to_isometry(transform: &Transform3D) -> Isometry<Real> {
+    let origin = transform.origin;
+    let rotation = transform.basis.orthonormalized().to_quat();
+    Isometry::<Real>::from_parts(
+        Translation::new(origin.x, origin.y, origin.z),
+        UnitQuaternion::from_quaternion(NaQuaternion::new(
+            rotation.w, rotation.x, rotation.y, rotation.z,
+        )),
+    )
+}
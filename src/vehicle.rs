@@ -0,0 +1,285 @@
+use godot::prelude::*;
+use rapier3d::{
+    control::{Wheel, WheelTuning},
+    prelude::*,
+};
+
+use crate::{rigid_body::R3DRigidBody, simulation::VehicleState, world::R3DWorld};
+
+#[derive(GodotClass)]
+#[class(base = Node3D)]
+pub struct R3DWheel {
+    #[export]
+    pub radius: f32,
+    #[export]
+    pub suspension_rest_length: f32,
+    #[export]
+    pub suspension_stiffness: f32,
+    #[export]
+    pub suspension_compression: f32,
+    #[export]
+    pub suspension_damping: f32,
+    #[export]
+    pub max_suspension_travel: f32,
+    #[export]
+    pub max_suspension_force: f32,
+    #[export]
+    pub friction_slip: f32,
+    #[export]
+    pub use_as_traction: bool,
+    #[export]
+    pub use_as_steering: bool,
+
+    index: Option<usize>,
+
+    node_3d: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for R3DWheel {
+    fn init(node_3d: Base<Node3D>) -> Self {
+        let tuning = WheelTuning::default();
+        Self {
+            radius: 0.5,
+            suspension_rest_length: 0.15,
+            suspension_stiffness: tuning.suspension_stiffness,
+            suspension_compression: tuning.suspension_compression,
+            suspension_damping: tuning.suspension_damping,
+            max_suspension_travel: tuning.max_suspension_travel,
+            max_suspension_force: tuning.max_suspension_force,
+            friction_slip: tuning.friction_slip,
+            use_as_traction: false,
+            use_as_steering: false,
+
+            index: None,
+
+            node_3d,
+        }
+    }
+}
+
+#[godot_api]
+impl R3DWheel {
+    #[func]
+    fn is_in_contact(&self) -> bool {
+        self.wheel()
+            .map(|wheel| wheel.raycast_info().is_in_contact)
+            .unwrap_or(false)
+    }
+
+    #[func]
+    fn suspension_length(&self) -> f32 {
+        let (Some(vehicle), Some(index)) = (self.vehicle(), self.index) else {
+            return self.suspension_rest_length;
+        };
+        vehicle
+            .bind()
+            .suspension_length(index)
+            .unwrap_or(self.suspension_rest_length)
+    }
+
+    #[func]
+    fn spin_rotation(&self) -> f32 {
+        self.wheel().map(|wheel| wheel.rotation).unwrap_or(0.0)
+    }
+
+    #[func]
+    fn steering(&self) -> f32 {
+        self.wheel().map(|wheel| wheel.steering).unwrap_or(0.0)
+    }
+
+    fn tuning(&self) -> WheelTuning {
+        WheelTuning {
+            suspension_stiffness: self.suspension_stiffness,
+            suspension_compression: self.suspension_compression,
+            suspension_damping: self.suspension_damping,
+            max_suspension_travel: self.max_suspension_travel,
+            friction_slip: self.friction_slip,
+            max_suspension_force: self.max_suspension_force,
+        }
+    }
+
+    fn vehicle(&self) -> Option<Gd<R3DVehicle>> {
+        self.index?;
+        self.base()
+            .get_parent()
+            .and_then(|parent| parent.try_cast::<R3DVehicle>().ok())
+    }
+
+    fn wheel(&self) -> Option<Wheel> {
+        let index = self.index?;
+        self.vehicle()?.bind().wheel(index)
+    }
+}
+
+/// Raycast vehicle driving the R3DRigidBody it is parented to. Wheels are
+/// taken from the R3DWheel children.
+#[derive(GodotClass)]
+#[class(base = Node3D)]
+pub struct R3DVehicle {
+    world: Option<Gd<R3DWorld>>,
+    key: Option<String>,
+    wheels: Vec<Gd<R3DWheel>>,
+
+    node_3d: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for R3DVehicle {
+    fn init(node_3d: Base<Node3D>) -> Self {
+        Self {
+            world: None,
+            key: None,
+            wheels: Vec::new(),
+
+            node_3d,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        // The parent body enters the tree and registers itself first
+        self.register_vehicle();
+    }
+
+    fn exit_tree(&mut self) {
+        self.unregister_vehicle();
+    }
+}
+
+#[godot_api]
+impl R3DVehicle {
+    #[func]
+    fn set_engine_force(&mut self, engine_force: f32) {
+        self.update_wheels(|wheel_node, wheel| {
+            if wheel_node.use_as_traction {
+                wheel.engine_force = engine_force;
+            }
+        });
+    }
+
+    #[func]
+    fn set_brake(&mut self, brake: f32) {
+        self.update_wheels(|_, wheel| {
+            wheel.brake = brake;
+        });
+    }
+
+    #[func]
+    fn set_steering(&mut self, steering: f32) {
+        self.update_wheels(|wheel_node, wheel| {
+            if wheel_node.use_as_steering {
+                wheel.steering = steering;
+            }
+        });
+    }
+
+    #[func]
+    fn current_speed(&self) -> f32 {
+        let (Some(world), Some(key)) = (self.world.as_ref(), self.key.as_ref()) else {
+            return 0.0;
+        };
+        world
+            .bind()
+            .vehicle(key)
+            .map(|vehicle| vehicle.current_vehicle_speed())
+            .unwrap_or(0.0)
+    }
+
+    fn wheel(&self, index: usize) -> Option<Wheel> {
+        let world = self.world.as_ref()?.bind();
+        let vehicle = world.vehicle(self.key.as_ref()?)?;
+        vehicle.wheels().get(index).copied()
+    }
+
+    fn suspension_length(&self, index: usize) -> Option<f32> {
+        let world = self.world.as_ref()?.bind();
+        let vehicle = world.vehicle(self.key.as_ref()?)?;
+        let wheel = vehicle.wheels().get(index)?;
+        let chassis = world.body(vehicle.chassis())?;
+        let hard_point = chassis.position() * wheel.chassis_connection_point_cs;
+        Some((wheel.center() - hard_point).norm())
+    }
+
+    fn update_wheels(&mut self, mut update: impl FnMut(&R3DWheel, &mut Wheel)) {
+        let (Some(world), Some(key)) = (self.world.as_mut(), self.key.as_ref()) else {
+            return;
+        };
+        let mut world = world.bind_mut();
+        let Some(vehicle) = world.vehicle_mut(key) else {
+            return;
+        };
+
+        for (wheel_node, wheel) in self.wheels.iter().zip(vehicle.wheels_mut()) {
+            update(&wheel_node.bind(), wheel);
+        }
+    }
+
+    fn register_vehicle(&mut self) {
+        let Some(handle) = self
+            .base()
+            .get_parent()
+            .and_then(|parent| parent.try_cast::<R3DRigidBody>().ok())
+            .and_then(|body| body.bind().handle())
+        else {
+            godot_error!(
+                "{} must be a child of a registered R3DRigidBody",
+                self.base().get_path()
+            );
+            return;
+        };
+
+        let Some(mut world) = R3DWorld::find(&self.base()) else {
+            return;
+        };
+
+        let key = self.base().get_path().to_string();
+        let vehicle_transform = self.base().get_transform();
+        let mut state = VehicleState::new(handle);
+        for child in self.base().get_children().iter_shared() {
+            let Ok(mut wheel_node) = child.try_cast::<R3DWheel>() else {
+                continue;
+            };
+
+            {
+                let wheel = wheel_node.bind();
+                let transform = vehicle_transform * wheel.base().get_transform();
+                let origin = transform.origin;
+                let direction = transform.basis * Vector3::DOWN;
+                let axle = transform.basis * Vector3::RIGHT;
+                state.add_wheel(
+                    point![origin.x, origin.y, origin.z],
+                    vector![direction.x, direction.y, direction.z].normalize(),
+                    vector![axle.x, axle.y, axle.z].normalize(),
+                    wheel.suspension_rest_length,
+                    wheel.radius,
+                    &wheel.tuning(),
+                );
+            }
+
+            wheel_node.bind_mut().index = Some(self.wheels.len());
+            self.wheels.push(wheel_node);
+        }
+
+        {
+            let mut world = world.bind_mut();
+            // After a rollback the vehicle may already exist with its
+            // simulated wheel state, which must be kept.
+            if world.vehicle(&key).is_none() {
+                world.add_vehicle(key.clone(), state);
+            }
+        }
+
+        self.key = Some(key);
+        self.world = Some(world);
+    }
+
+    fn unregister_vehicle(&mut self) {
+        for mut wheel in self.wheels.drain(..) {
+            wheel.bind_mut().index = None;
+        }
+
+        if let (Some(mut world), Some(key)) = (self.world.take(), self.key.take()) {
+            world.bind_mut().remove_vehicle(&key);
+        }
+    }
+}
@@ -1,45 +1,19 @@
-use std::collections::HashMap;
-
-use godot::prelude::*;
-use rapier3d::{
-    control::{EffectiveCharacterMovement, KinematicCharacterController},
-    na::{Isometry3, UnitQuaternion},
-    prelude::*,
-};
+use godot::{engine::ProjectSettings, prelude::*};
+use rapier3d::{control::EffectiveCharacterMovement, na::UnitQuaternion, prelude::*};
 
 use crate::{
     raycast_result::RaycastResult,
-    rigid_body::{BodyType, R3DRigidBody},
-    utils::isometry_to_transform,
+    rigid_body::R3DRigidBody,
+    simulation::{Simulation, VehicleState, WorldState},
+    utils::{isometry_to_transform, sync_manager_log, transform_to_isometry},
 };
 
-#[derive(Clone)]
-struct WorldState {
-    rigid_body_set: RigidBodySet,
-    collider_set: ColliderSet,
-
-    // Simulation structures
-    gravity: Vector3,
-    integration_parameters: IntegrationParameters,
-    query_pipeline: QueryPipeline,
-    island_manager: IslandManager,
-    broad_phase: BroadPhase,
-    narrow_phase: NarrowPhase,
-    impulse_joint_set: ImpulseJointSet,
-    multibody_joint_set: MultibodyJointSet,
-    ccd_solver: CCDSolver,
-
-    // State
-    godot_body_node_lookup: HashMap<RigidBodyHandle, String>,
-    body_handle_lookup: HashMap<String, RigidBodyHandle>,
-}
-
+/// Autoload that exposes the rollback simulation to Godot. Bodies are keyed
+/// by the path of their R3DRigidBody node.
 #[derive(GodotClass)]
 #[class(base = Node)]
 pub struct R3DWorld {
-    current_tick: u32,
-    frames: HashMap<u32, WorldState>,
-    physics_pipeline: PhysicsPipeline,
+    simulation: Simulation,
 
     node: Base<Node>,
 }
@@ -47,34 +21,8 @@ pub struct R3DWorld {
 #[godot_api]
 impl INode for R3DWorld {
     fn init(node: Base<Node>) -> Self {
-        let mut frames = HashMap::new();
-        frames.insert(
-            0,
-            WorldState {
-                rigid_body_set: RigidBodySet::new(),
-                collider_set: ColliderSet::new(),
-
-                // Simulation structures
-                gravity: Vector3::new(0., -9.81, 0.),
-                integration_parameters: IntegrationParameters::default(),
-                query_pipeline: QueryPipeline::new(),
-                island_manager: IslandManager::new(),
-                broad_phase: BroadPhase::new(),
-                narrow_phase: NarrowPhase::new(),
-                impulse_joint_set: ImpulseJointSet::new(),
-                multibody_joint_set: MultibodyJointSet::new(),
-                ccd_solver: CCDSolver::new(),
-
-                // State
-                godot_body_node_lookup: HashMap::new(),
-                body_handle_lookup: HashMap::new(),
-            },
-        );
-
         Self {
-            current_tick: 0,
-            frames,
-            physics_pipeline: PhysicsPipeline::new(),
+            simulation: Simulation::new(),
 
             node,
         }
@@ -89,188 +37,233 @@ impl INode for R3DWorld {
 impl R3DWorld {
     #[func]
     pub fn load_state(&mut self, tick: u32) {
-        self.current_tick = tick;
-        self.frames.retain(|k, _| *k <= self.current_tick);
+        if !self.simulation.load_state(tick) {
+            godot_error!("Can't roll back to tick {tick}, it was never simulated");
+            return;
+        }
+
+        // Don't interpolate from transforms of the discarded ticks
+        let frame = self.simulation.state();
+        for (handle, _, body) in frame.tracked_bodies() {
+            if let Some(mut godot_node) = self.body_node(frame, handle) {
+                if body.is_kinematic() && godot_node.bind().follow_node_transform {
+                    // The node holds the game's current target, not a pose
+                    // from the discarded ticks
+                    continue;
+                }
+                let transform = isometry_to_transform(body.position());
+                godot_node.bind_mut().rollback(transform);
+            }
+        }
     }
 
     #[func]
     pub fn networked_preprocess(&self) {
-        let frame = self.frames.get(&self.current_tick).unwrap();
-        for (handle, body) in frame.rigid_body_set.iter() {
-            if let Some(godot_node_path) = frame.godot_body_node_lookup.get(&handle) {
-                if let Some(mut godot_node) = self
-                    .base()
-                    .get_node(godot_node_path.into())
-                    .map(|node| node.cast::<R3DRigidBody>())
-                {
-                    let transform = isometry_to_transform(body.position());
-                    godot_node.set_transform(transform);
+        let frame = self.simulation.state();
+        for (handle, _, body) in frame.tracked_bodies() {
+            if let Some(mut godot_node) = self.body_node(frame, handle) {
+                if body.is_kinematic() && godot_node.bind().follow_node_transform {
+                    // The node drives this body, so leave its transform alone
+                    continue;
                 }
+                let transform = isometry_to_transform(body.position());
+                godot_node.set_transform(transform);
             }
         }
     }
 
     #[func]
     pub fn networked_process(&mut self) -> u32 {
-        let mut frame = self.frames.get_mut(&self.current_tick).unwrap().clone();
-        self.physics_pipeline.step(
-            &vector![frame.gravity.x, frame.gravity.y, frame.gravity.z],
-            &frame.integration_parameters,
-            &mut frame.island_manager,
-            &mut frame.broad_phase,
-            &mut frame.narrow_phase,
-            &mut frame.rigid_body_set,
-            &mut frame.collider_set,
-            &mut frame.impulse_joint_set,
-            &mut frame.multibody_joint_set,
-            &mut frame.ccd_solver,
-            None,
-            &(),
-            &(),
-        );
-        frame
-            .query_pipeline
-            .update(&frame.rigid_body_set, &frame.collider_set);
-        for (handle, body) in frame.rigid_body_set.iter_mut() {
-            body.reset_forces(true);
-            body.reset_torques(true);
-
-            if let Some(godot_node_path) = frame.godot_body_node_lookup.get(&handle) {
-                if let Some(mut godot_node) = self
-                    .base()
-                    .get_node(godot_node_path.into())
-                    .map(|node| node.cast::<R3DRigidBody>())
+        let base = self.base().clone();
+        let tick = self.simulation.step_with(|frame| {
+            for (_, node_path, body) in frame.tracked_bodies_mut() {
+                if !body.is_kinematic() {
+                    continue;
+                }
+                if let Some(godot_node) = base
+                    .get_node_or_null(node_path.into())
+                    .and_then(|node| node.try_cast::<R3DRigidBody>().ok())
                 {
-                    let transform = isometry_to_transform(body.position());
-                    godot_node.set_transform(transform);
-                } else {
-                    let mut sync_manager =
-                        self.base().get_node("/root/SyncManager".into()).unwrap();
-                    sync_manager.call(
-                        "log".into(),
-                        &[format!(
-                            "Tracked Node Not Found on tick {} {handle:?}",
-                            self.current_tick
-                        )
-                        .to_variant()],
-                    );
-                    panic!("Tracked node not found. Maybe it was deleted without chance to unregister?");
+                    if godot_node.bind().follow_node_transform {
+                        let position = transform_to_isometry(&godot_node.get_transform());
+                        body.set_next_kinematic_position(position);
+                    }
                 }
             }
+        });
+
+        let frame = self.simulation.state();
+        for (handle, node_path, body) in frame.tracked_bodies() {
+            if let Some(mut godot_node) = base
+                .get_node_or_null(node_path.into())
+                .and_then(|node| node.try_cast::<R3DRigidBody>().ok())
+            {
+                let transform = isometry_to_transform(body.position());
+                godot_node.bind_mut().push_tick_transform(transform);
+            } else {
+                sync_manager_log(
+                    &base,
+                    format!("Tracked Node Not Found on tick {tick} {handle:?}"),
+                );
+                godot_error!(
+                    "Tracked node {node_path} not found. Maybe it was deleted without chance to unregister?"
+                );
+            }
         }
 
-        self.current_tick += 1;
-        self.frames.insert(self.current_tick, frame);
-        self.current_tick
+        tick
     }
 
     #[func]
     pub fn bodies_within_sphere(&self, position: Vector3, radius: f32) -> Array<Gd<R3DRigidBody>> {
-        let frame = self.frames.get(&self.current_tick).unwrap();
-
-        let mut intersecting_colliders = Vec::new();
-        frame.query_pipeline.intersections_with_shape(
-            &frame.rigid_body_set,
-            &frame.collider_set,
-            &Isometry3::<f32>::translation(position.x, position.y, position.z),
-            &Ball::new(radius),
-            QueryFilter::only_dynamic(),
-            |collider| {
-                intersecting_colliders.push(collider);
-                true
-            },
-        );
-
-        intersecting_colliders
+        let frame = self.simulation.state();
+        self.simulation
+            .bodies_within_sphere(point![position.x, position.y, position.z], radius)
             .into_iter()
-            .map(|collider_handle| {
-                let collider = frame
-                    .collider_set
-                    .get(collider_handle)
-                    .expect("Collider missing");
-                let body = collider.parent().expect("Collider has no parent");
-                let node_path = frame
-                    .godot_body_node_lookup
-                    .get(&body)
-                    .expect("Body not found");
-                self.base()
-                    .get_node(node_path.into())
-                    .expect("Node not found")
-                    .cast::<R3DRigidBody>()
-            })
+            // Bodies without a node, such as ragdoll bones, are skipped
+            .filter_map(|body| self.body_node(frame, body))
             .collect()
     }
 }
 
+/// Project setting that lets the editor plugin add the R3DWorld autoload.
+/// Turning it off removes the autoload again.
+pub const AUTOLOAD_ENABLED_SETTING: &str = "gdrapier3d/autoload/enabled";
+/// Project setting with the name of the R3DWorld autoload.
+pub const AUTOLOAD_NAME_SETTING: &str = "gdrapier3d/autoload/name";
+/// Project setting with the scene the R3DWorld autoload is saved to.
+pub const AUTOLOAD_PATH_SETTING: &str = "gdrapier3d/autoload/path";
+pub const DEFAULT_AUTOLOAD_NAME: &str = "World";
+pub const DEFAULT_AUTOLOAD_PATH: &str = "res://autoloads/world.tscn";
+
 impl R3DWorld {
-    pub fn body<'a>(&'a self, handle: RigidBodyHandle) -> Option<&'a RigidBody> {
-        let frame = self.frames.get(&self.current_tick).unwrap();
-        frame.rigid_body_set.get(handle)
+    /// The configured name of the R3DWorld autoload.
+    pub fn autoload_name() -> GString {
+        let project_settings = ProjectSettings::singleton();
+        if project_settings.has_setting(AUTOLOAD_NAME_SETTING.into()) {
+            project_settings
+                .get_setting(AUTOLOAD_NAME_SETTING.into())
+                .to::<GString>()
+        } else {
+            DEFAULT_AUTOLOAD_NAME.into()
+        }
+    }
+
+    /// Looks up the R3DWorld autoload, reporting an error when it is missing.
+    pub fn find(node: &Node) -> Option<Gd<R3DWorld>> {
+        let path = format!("/root/{}", Self::autoload_name());
+        let world = node
+            .get_node_or_null(path.as_str().into())
+            .and_then(|world| world.try_cast::<R3DWorld>().ok());
+        if world.is_none() {
+            godot_error!(
+                "{} could not find the R3DWorld autoload at {}",
+                node.get_path(),
+                path
+            );
+        }
+        world
     }
 
-    pub fn body_mut<'a>(&'a mut self, handle: RigidBodyHandle) -> Option<&'a mut RigidBody> {
-        let frame = self.frames.get_mut(&self.current_tick).unwrap();
-        frame.rigid_body_set.get_mut(handle)
+    fn body_node(&self, frame: &WorldState, body: RigidBodyHandle) -> Option<Gd<R3DRigidBody>> {
+        let node_path = frame.tracked_key(body)?;
+        let node = self
+            .base()
+            .get_node_or_null(node_path.into())
+            .and_then(|node| node.try_cast::<R3DRigidBody>().ok());
+        if node.is_none() {
+            godot_error!("Tracked node {node_path} not found");
+        }
+        node
+    }
+
+    pub fn simulation(&self) -> &Simulation {
+        &self.simulation
+    }
+
+    pub fn body(&self, handle: RigidBodyHandle) -> Option<&RigidBody> {
+        self.simulation.body(handle)
+    }
+
+    pub fn body_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody> {
+        self.simulation.body_mut(handle)
     }
 
     pub fn add_body(&mut self, godot_node: &mut R3DRigidBody) -> RigidBodyHandle {
-        let frame = self.frames.get_mut(&self.current_tick).unwrap();
         let node_path = godot_node.base().get_path().to_string();
-        if let Some(handle) = frame.body_handle_lookup.get(&node_path) {
+        if let Some(handle) = self.simulation.find_body(&node_path) {
             // Body already exists, pull the existing handle and update node position
-            let body = frame.rigid_body_set.get(*handle).unwrap();
-            let transform = isometry_to_transform(body.position());
-            godot_node.base_mut().set_transform(transform);
-            *handle
+            if let Some(body) = self.simulation.body(handle) {
+                let transform = isometry_to_transform(body.position());
+                godot_node.base_mut().set_transform(transform);
+            }
+            handle
         } else {
             // Body does not exist. Create a new one and set its position
-            let body_type = match godot_node.body_type {
-                BodyType::Dynamic => RigidBodyType::Dynamic,
-                BodyType::Fixed => RigidBodyType::Fixed,
-                BodyType::Kinematic => RigidBodyType::KinematicPositionBased,
-            };
             let translation = godot_node.base().get_position();
             let rotation = godot_node.base().get_rotation();
             let position = Isometry::<Real>::from_parts(
                 Translation::new(translation.x, translation.y, translation.z),
                 UnitQuaternion::from_euler_angles(rotation.x, rotation.y, rotation.z),
             );
-            let body = RigidBodyBuilder::new(body_type).position(position).build();
-            let handle = frame.rigid_body_set.insert(body);
-            frame
-                .godot_body_node_lookup
-                .insert(handle, node_path.clone());
-            frame.body_handle_lookup.insert(node_path, handle);
-            let mut sync_manager = self.base().get_node("/root/SyncManager".into()).unwrap();
-            sync_manager.call(
-                "log".into(),
-                &[format!("Added Body on Tick {} {handle:?}", self.current_tick).to_variant()],
+            let body = godot_node.body_builder().position(position).build();
+            let handle = self.simulation.insert_tracked_body(node_path, body);
+            sync_manager_log(
+                &self.base(),
+                format!(
+                    "Added Body on Tick {} {handle:?}",
+                    self.simulation.current_tick()
+                ),
             );
             handle
         }
     }
 
     pub fn remove_body(&mut self, handle: RigidBodyHandle) {
-        let mut sync_manager = self.base().get_node("/root/SyncManager".into()).unwrap();
-        sync_manager.call(
-            "log".into(),
-            &[format!("Removing Body {handle:?}").to_variant()],
-        );
-        let frame = self.frames.get_mut(&self.current_tick).unwrap();
-        frame.godot_body_node_lookup.remove(&handle);
-        frame.rigid_body_set.remove(
-            handle,
-            &mut frame.island_manager,
-            &mut frame.collider_set,
-            &mut frame.impulse_joint_set,
-            &mut frame.multibody_joint_set,
-            false,
+        sync_manager_log(&self.base(), format!("Removing Body {handle:?}"));
+        self.simulation.remove_body(handle);
+        sync_manager_log(
+            &self.base(),
+            format!(
+                "Removed Body on Tick {} {handle:?}",
+                self.simulation.current_tick()
+            ),
         );
+    }
 
-        sync_manager.call(
-            "log".into(),
-            &[format!("Removed Body on Tick {} {handle:?}", self.current_tick).to_variant()],
-        );
+    pub fn find_body(&self, key: &str) -> Option<RigidBodyHandle> {
+        self.simulation.find_body(key)
+    }
+
+    /// Inserts a body that is not backed by its own R3DRigidBody node. The key
+    /// must be stable across peers so that rollbacks can find the body again.
+    pub fn insert_body(&mut self, key: String, body: RigidBody) -> RigidBodyHandle {
+        self.simulation.insert_body(key, body)
+    }
+
+    pub fn add_impulse_joint(
+        &mut self,
+        body1: RigidBodyHandle,
+        body2: RigidBodyHandle,
+        joint: impl Into<GenericJoint>,
+    ) -> ImpulseJointHandle {
+        self.simulation.add_impulse_joint(body1, body2, joint)
+    }
+
+    pub fn vehicle(&self, key: &str) -> Option<&VehicleState> {
+        self.simulation.vehicle(key)
+    }
+
+    pub fn vehicle_mut(&mut self, key: &str) -> Option<&mut VehicleState> {
+        self.simulation.vehicle_mut(key)
+    }
+
+    pub fn add_vehicle(&mut self, key: String, vehicle: VehicleState) {
+        self.simulation.add_vehicle(key, vehicle);
+    }
+
+    pub fn remove_vehicle(&mut self, key: &str) {
+        self.simulation.remove_vehicle(key);
     }
 
     pub fn add_collider_to_body(
@@ -278,20 +271,28 @@ impl R3DWorld {
         collider: Collider,
         body: RigidBodyHandle,
     ) -> ColliderHandle {
-        let frame = self.frames.get_mut(&self.current_tick).unwrap();
-        frame
-            .collider_set
-            .insert_with_parent(collider, body, &mut frame.rigid_body_set)
+        self.simulation.add_collider_to_body(collider, body)
+    }
+
+    /// Inserts a static collider that isn't attached to any body.
+    pub fn add_collider(&mut self, collider: Collider) -> ColliderHandle {
+        self.simulation.add_collider(collider)
+    }
+
+    pub fn collider_mut(&mut self, handle: ColliderHandle) -> Option<&mut Collider> {
+        self.simulation.collider_mut(handle)
+    }
+
+    pub fn debug_render(
+        &self,
+        pipeline: &mut DebugRenderPipeline,
+        backend: &mut impl DebugRenderBackend,
+    ) {
+        self.simulation.debug_render(pipeline, backend);
     }
 
     pub fn remove_collider(&mut self, handle: ColliderHandle) -> Option<Collider> {
-        let frame = self.frames.get_mut(&self.current_tick).unwrap();
-        frame.collider_set.remove(
-            handle,
-            &mut frame.island_manager,
-            &mut frame.rigid_body_set,
-            true,
-        )
+        self.simulation.remove_collider(handle)
     }
 
     pub fn move_shape(
@@ -303,18 +304,12 @@ impl R3DWorld {
 
         body_handle: RigidBodyHandle,
     ) -> EffectiveCharacterMovement {
-        let frame = self.frames.get(&self.current_tick).unwrap();
-        let character_controller = KinematicCharacterController::default();
-        character_controller.move_shape(
+        self.simulation.move_shape(
             dt,
-            &frame.rigid_body_set,
-            &frame.collider_set,
-            &frame.query_pipeline,
             character_shape,
             character_pos,
             desired_translation,
-            QueryFilter::default().exclude_rigid_body(body_handle),
-            |_| {},
+            body_handle,
         )
     }
 
@@ -324,41 +319,15 @@ impl R3DWorld {
         direction: Vector<f32>,
         max_distance: f32,
     ) -> RaycastResult {
-        let frame = self.frames.get(&self.current_tick).unwrap();
-        let position = frame.rigid_body_set.get(body).unwrap().translation();
-        let ray = Ray::new(
-            Point::new(position.x, position.y, position.z),
-            direction.normalize(),
-        );
-
-        let Some((collider, distance)) = frame.query_pipeline.cast_ray(
-            &frame.rigid_body_set,
-            &frame.collider_set,
-            &ray,
-            max_distance,
-            true,
-            QueryFilter::default().exclude_rigid_body(body),
-        ) else {
+        let Some(hit) = self.simulation.cast_ray(body, direction, max_distance) else {
             return RaycastResult::None;
         };
 
-        let collider = frame
-            .collider_set
-            .get(collider)
-            .expect("Collider not found");
-        let body = collider.parent().expect("Collider has no parent");
-        let node_path = frame
-            .godot_body_node_lookup
-            .get(&body)
-            .expect("Body not found");
-        let node = self
-            .base()
-            .get_node(node_path.into())
-            .expect("Node not found")
-            .cast::<R3DRigidBody>();
+        // Static colliders and bodies without a node have no body to report
+        let frame = self.simulation.state();
         RaycastResult::Hit {
-            body: node.clone(),
-            distance,
+            body: hit.body.and_then(|body| self.body_node(frame, body)),
+            distance: hit.distance,
         }
     }
 }